@@ -1,3 +1,4 @@
 pub mod ed25519;
 pub mod ring_vrf;
+pub mod seal;
 pub mod sign;
@@ -11,6 +11,50 @@ use std::ptr;
 use ark_vrf::suites::bandersnatch::Public;
 use ark_vrf::suites::bandersnatch::Secret;
 
+/// Decodes a ring of public keys, keys that are not on the curve are replaced
+/// with the padding point
+///
+/// When `padded_out` is not null, one flag per key is written to it telling
+/// whether that key was replaced. Returns None, before writing any flags, if
+/// the keys are not a whole number of `PUBLIC_KEY_SIZE` bytes or there is no
+/// ring context for the ring size.
+///
+/// # Safety
+/// - `padded_out` must be null or point to one `bool` per key
+unsafe fn decode_ring(
+  public_keys: &[u8],
+  padded_out: *mut bool,
+) -> Option<Vec<Public>> {
+  if public_keys.len() % PUBLIC_KEY_SIZE != 0 {
+    return None;
+  }
+  let num_keys = public_keys.len() / PUBLIC_KEY_SIZE;
+  let padding_point = match ring_context(num_keys) {
+    Ok(_) => ark_vrf::ring::RingProofParams::<
+      ark_vrf::suites::bandersnatch::BandersnatchSha512Ell2,
+    >::padding_point(),
+    Err(_) => return None,
+  };
+  // Using deserialize_compressed_unchecked instead of deserialize_compressed
+  // to accept any valid point on the Bandersnatch curve, not just those in the prime subgroup.
+  // This matches davxy and Parity's implementation and is safe because:
+  // 1. Invalid keys should never reach JAM (filtered by PoP verification beforehand)
+  // 2. It's faster and prevents replacement with padding points that would alter VRF outputs
+  // See: JAM conformance test 1754990132 gamma.z difference
+  let ring = public_keys
+    .chunks_exact(PUBLIC_KEY_SIZE)
+    .enumerate()
+    .map(|(i, chunk)| {
+      let key = Public::deserialize_compressed_unchecked(chunk).ok();
+      if !padded_out.is_null() {
+        *padded_out.add(i) = key.is_none();
+      }
+      key.unwrap_or(Public::from(padding_point))
+    })
+    .collect();
+  Some(ring)
+}
+
 /// Create a new Ring VRF Verifier.
 ///
 /// The ring size is determined by the number of public keys passed (public_keys_len / PUBLIC_KEY_SIZE).
@@ -23,6 +67,27 @@ use ark_vrf::suites::bandersnatch::Secret;
 pub unsafe extern "C" fn new_ring_vrf_verifier(
   public_keys: *const u8,
   public_keys_len: size_t,
+) -> *mut Verifier {
+  new_ring_vrf_verifier_with_padding_report(
+    public_keys,
+    public_keys_len,
+    ptr::null_mut(),
+  )
+}
+
+/// Create a new Ring VRF Verifier, reporting replaced keys.
+///
+/// Same as `new_ring_vrf_verifier`. When `padded_out` is not null, it receives one flag per key
+/// telling whether that key was replaced with the padding point.
+///
+/// # Safety
+/// - `public_keys` must point to a contiguous array of serialized public keys
+/// - `padded_out` must be null or point to one `bool` per public key
+#[no_mangle]
+pub unsafe extern "C" fn new_ring_vrf_verifier_with_padding_report(
+  public_keys: *const u8,
+  public_keys_len: size_t,
+  padded_out: *mut bool,
 ) -> *mut Verifier {
   debug_assert!(
     !public_keys.is_null(),
@@ -35,31 +100,10 @@ pub unsafe extern "C" fn new_ring_vrf_verifier(
 
   let public_keys_slice =
     std::slice::from_raw_parts(public_keys, public_keys_len);
-  let num_keys = public_keys_len / PUBLIC_KEY_SIZE;
-
-  let padding_point = match ring_context(num_keys) {
-    Ok(_) => ark_vrf::ring::RingProofParams::<
-      ark_vrf::suites::bandersnatch::BandersnatchSha512Ell2,
-    >::padding_point(),
-    Err(_) => return std::ptr::null_mut(),
-  };
-  // Using deserialize_compressed_unchecked instead of deserialize_compressed
-  // to accept any valid point on the Bandersnatch curve, not just those in the prime subgroup.
-  // This matches davxy and Parity's implementation and is safe because:
-  // 1. Invalid keys should never reach JAM (filtered by PoP verification beforehand)
-  // 2. It's faster and prevents replacement with padding points that would alter VRF outputs
-  // See: JAM conformance test 1754990132 gamma.z difference
-  let ring: Vec<Public> = public_keys_slice
-    .chunks(PUBLIC_KEY_SIZE)
-    .map(|chunk| {
-      Public::deserialize_compressed_unchecked(chunk)
-        .unwrap_or(Public::from(padding_point))
-    })
-    .collect();
 
-  if ring.len() != num_keys {
+  let Some(ring) = decode_ring(public_keys_slice, padded_out) else {
     return std::ptr::null_mut();
-  }
+  };
 
   match Verifier::new(ring) {
     Ok(verifier) => Box::into_raw(Box::new(verifier)),
@@ -87,6 +131,31 @@ pub unsafe extern "C" fn new_ring_vrf_prover(
   public_keys: *const u8,
   public_keys_len: size_t,
   prover_idx: size_t,
+) -> *mut Prover {
+  new_ring_vrf_prover_with_padding_report(
+    secret,
+    public_keys,
+    public_keys_len,
+    prover_idx,
+    ptr::null_mut(),
+  )
+}
+
+/// Create a new Ring VRF Prover, reporting replaced keys.
+///
+/// Same as `new_ring_vrf_prover`, reporting replaced keys through
+/// `padded_out` as in `new_ring_vrf_verifier_with_padding_report`.
+///
+/// # Safety
+/// - All pointers must be valid and point to sufficient memory
+/// - `padded_out` must be null or point to one `bool` per public key
+#[no_mangle]
+pub unsafe extern "C" fn new_ring_vrf_prover_with_padding_report(
+  secret: *const u8,
+  public_keys: *const u8,
+  public_keys_len: size_t,
+  prover_idx: size_t,
+  padded_out: *mut bool,
 ) -> *mut Prover {
   debug_assert!(!secret.is_null(), "secret pointer must not be null");
   debug_assert!(
@@ -108,25 +177,9 @@ pub unsafe extern "C" fn new_ring_vrf_prover(
     return std::ptr::null_mut();
   };
 
-  let padding_point = match ring_context(public_keys_len / PUBLIC_KEY_SIZE) {
-    Ok(_) => ark_vrf::ring::RingProofParams::<
-      ark_vrf::suites::bandersnatch::BandersnatchSha512Ell2,
-    >::padding_point(),
-    Err(_) => return std::ptr::null_mut(),
+  let Some(ring) = decode_ring(public_keys_slice, padded_out) else {
+    return std::ptr::null_mut();
   };
-  // Using deserialize_compressed_unchecked instead of deserialize_compressed
-  // to accept any valid point on the Bandersnatch curve, not just those in the prime subgroup.
-  // This matches davxy and Parity's implementation and is safe because:
-  // 1. Invalid keys should never reach JAM (filtered by PoP verification beforehand)
-  // 2. It's faster and prevents replacement with padding points that would alter VRF outputs
-  // See: JAM conformance test 1754990132 gamma.z difference
-  let ring: Vec<Public> = public_keys_slice
-    .chunks(PUBLIC_KEY_SIZE)
-    .map(|chunk| {
-      Public::deserialize_compressed_unchecked(chunk)
-        .unwrap_or(Public::from(padding_point))
-    })
-    .collect();
 
   Box::into_raw(Box::new(Prover::new(ring, secret, prover_idx)))
 }
@@ -458,6 +511,26 @@ fn serialize_key_pair(secret: &Secret, public_key: &Public) -> Option<Vec<u8>> {
   Some(serialized)
 }
 
+/// Classifies a public key as invalid, on the curve only, or in the
+/// prime-order subgroup.
+///
+/// Ring construction accepts any key on the curve, this lets validator set
+/// updates reject keys explicitly instead.
+///
+/// # Safety
+/// - `public_key` must point to `PUBLIC_KEY_SIZE` bytes
+#[no_mangle]
+pub unsafe extern "C" fn classify_public_key(
+  public_key: *const u8,
+) -> KeyValidity {
+  debug_assert!(!public_key.is_null(), "public_key pointer must not be null");
+
+  super::types::classify_public_key(std::slice::from_raw_parts(
+    public_key,
+    PUBLIC_KEY_SIZE,
+  ))
+}
+
 /// # Safety
 #[no_mangle]
 pub unsafe extern "C" fn get_padding_point(
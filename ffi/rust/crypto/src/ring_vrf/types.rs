@@ -33,3 +33,52 @@ pub struct RingVrfSignature {
   /// This contains both the Pedersen proof and actual ring proof.
   pub proof: RingProof,
 }
+
+/// Validity of a compressed Bandersnatch public key
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyValidity {
+  /// Not the encoding of a curve point
+  Invalid = 0,
+  /// On the curve but outside the prime-order subgroup
+  OnCurve = 1,
+  /// In the prime-order subgroup
+  InPrimeSubgroup = 2,
+}
+
+/// Classifies a public key, compressed decoding always checks that the point
+/// is on the curve and only the checked variant tests the subgroup
+pub fn classify_public_key(public_key: &[u8]) -> KeyValidity {
+  if Public::deserialize_compressed(public_key).is_ok() {
+    KeyValidity::InPrimeSubgroup
+  } else if Public::deserialize_compressed_unchecked(public_key).is_ok() {
+    KeyValidity::OnCurve
+  } else {
+    KeyValidity::Invalid
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_classify_public_key() {
+    let public = Secret::from_seed(&[1u8; 32]).public();
+    let mut encoded = Vec::new();
+    public.serialize_compressed(&mut encoded).unwrap();
+    assert_eq!(classify_public_key(&encoded), KeyValidity::InPrimeSubgroup);
+
+    // A coordinate above the field modulus is no point at all
+    assert_eq!(classify_public_key(&[0xff; 32]), KeyValidity::Invalid);
+
+    // (0, -1) has order 2, it is on the curve but not in the prime subgroup
+    let mut small_order = [0u8; 32];
+    small_order[4..].copy_from_slice(&[
+      0xff, 0xff, 0xff, 0xff, 0xfe, 0x5b, 0xfe, 0xff, 0x02, 0xa4, 0xbd, 0x53,
+      0x05, 0xd8, 0xa1, 0x09, 0x08, 0xd8, 0x39, 0x33, 0x48, 0x7d, 0x9d, 0x29,
+      0x53, 0xa7, 0xed, 0x73,
+    ]);
+    assert_eq!(classify_public_key(&small_order), KeyValidity::OnCurve);
+  }
+}
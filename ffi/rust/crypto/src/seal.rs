@@ -0,0 +1,541 @@
+use ark_vrf::reexports::ark_serialize::CanonicalDeserialize;
+use ark_vrf::reexports::ark_serialize::CanonicalSerialize;
+use ark_vrf::suites::bandersnatch::*;
+use libc::{c_int, size_t};
+use std::ptr;
+use std::slice;
+
+use crate::sign::{
+  bandersnatch_sign_impl, bandersnatch_verify_impl,
+  bandersnatch_vrf_output_impl, BandersnatchSignature, OUTPUT_LENGTH,
+  PUBLIC_LENGTH, SECRET_LENGTH, SIGNATURE_LENGTH,
+};
+
+// Context strings X_T, X_F and X_E defined in section 6.4 of the whitepaper
+const SEAL_CONTEXT_TICKET: &[u8] = b"jam_ticket_seal";
+const SEAL_CONTEXT_FALLBACK: &[u8] = b"jam_fallback_seal";
+const ENTROPY_CONTEXT: &[u8] = b"jam_entropy";
+
+const ENTROPY_LENGTH: usize = 32;
+
+/// How the block was sealed, selected by the slot sealer of the epoch
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SealMode {
+  /// Sealed with a ticket, carries the ticket's attempt index
+  Ticket(u8),
+  /// Sealed with the author's key in fallback mode
+  Fallback,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SealError {
+  SealVerificationFailed,
+  EntropySourceVerificationFailed,
+}
+
+/// Builds the seal VRF input X_T ⌢ η'_3 ⌢ i_r for tickets, or X_F ⌢ η'_3
+/// in fallback mode
+fn seal_vrf_input(entropy: &[u8; ENTROPY_LENGTH], mode: SealMode) -> Vec<u8> {
+  let mut input = Vec::with_capacity(SEAL_CONTEXT_FALLBACK.len() + 33);
+  match mode {
+    SealMode::Ticket(attempt) => {
+      input.extend_from_slice(SEAL_CONTEXT_TICKET);
+      input.extend_from_slice(entropy);
+      input.push(attempt);
+    }
+    SealMode::Fallback => {
+      input.extend_from_slice(SEAL_CONTEXT_FALLBACK);
+      input.extend_from_slice(entropy);
+    }
+  }
+  input
+}
+
+/// Builds the entropy source VRF input X_E ⌢ Y(H_s)
+fn entropy_vrf_input(seal_output: &[u8; OUTPUT_LENGTH]) -> Vec<u8> {
+  [ENTROPY_CONTEXT, &seal_output[..]].concat()
+}
+
+/// Verifies the seal H_s over the unsealed header, returns Y(H_s)
+pub(crate) fn verify_seal_signature_impl(
+  public: Public,
+  unsealed_header: &[u8],
+  entropy: &[u8; ENTROPY_LENGTH],
+  mode: SealMode,
+  seal: BandersnatchSignature,
+) -> Result<[u8; OUTPUT_LENGTH], SealError> {
+  bandersnatch_verify_impl(
+    public,
+    &seal_vrf_input(entropy, mode),
+    unsealed_header,
+    seal,
+  )
+  .map_err(|_| SealError::SealVerificationFailed)
+}
+
+/// Verifies the entropy source H_v over the seal output Y(H_s), returns
+/// Y(H_v)
+pub(crate) fn verify_entropy_source_impl(
+  public: Public,
+  seal_output: &[u8; OUTPUT_LENGTH],
+  entropy_source: BandersnatchSignature,
+) -> Result<[u8; OUTPUT_LENGTH], SealError> {
+  bandersnatch_verify_impl(
+    public,
+    &entropy_vrf_input(seal_output),
+    &[],
+    entropy_source,
+  )
+  .map_err(|_| SealError::EntropySourceVerificationFailed)
+}
+
+/// Verifies the seal H_s over the unsealed header and then the entropy source
+/// H_v over the seal output
+///
+/// Returns Y(H_s) and Y(H_v) on success
+pub(crate) fn verify_seal_impl(
+  public: Public,
+  unsealed_header: &[u8],
+  entropy: &[u8; ENTROPY_LENGTH],
+  mode: SealMode,
+  seal: BandersnatchSignature,
+  entropy_source: BandersnatchSignature,
+) -> Result<([u8; OUTPUT_LENGTH], [u8; OUTPUT_LENGTH]), SealError> {
+  let seal_output = verify_seal_signature_impl(
+    public.clone(),
+    unsealed_header,
+    entropy,
+    mode,
+    seal,
+  )?;
+  let entropy_output =
+    verify_entropy_source_impl(public, &seal_output, entropy_source)?;
+
+  Ok((seal_output, entropy_output))
+}
+
+/// Signs the entropy source H_v over Y(H_s) and then the seal H_s over the
+/// unsealed header
+///
+/// H_v is part of the unsealed header, so it is written into
+/// `unsealed_header` at `entropy_source_offset` before sealing. Y(H_s) only
+/// depends on the seal VRF input, which is what lets H_v come first.
+///
+/// Returns H_v and H_s
+pub(crate) fn sign_seal_impl(
+  secret: &Secret,
+  unsealed_header: &mut [u8],
+  entropy_source_offset: usize,
+  entropy: &[u8; ENTROPY_LENGTH],
+  mode: SealMode,
+) -> Result<(BandersnatchSignature, BandersnatchSignature), ()> {
+  let seal_input = seal_vrf_input(entropy, mode);
+  let seal_output = bandersnatch_vrf_output_impl(secret, &seal_input);
+
+  let entropy_source = bandersnatch_sign_impl(
+    secret.clone(),
+    &entropy_vrf_input(&seal_output),
+    &[],
+  );
+  let entropy_source_bytes = unsealed_header
+    .get_mut(entropy_source_offset..)
+    .and_then(|bytes| bytes.get_mut(..SIGNATURE_LENGTH))
+    .ok_or(())?;
+  entropy_source
+    .serialize_compressed(entropy_source_bytes)
+    .map_err(|_| ())?;
+
+  let seal =
+    bandersnatch_sign_impl(secret.clone(), &seal_input, unsealed_header);
+  Ok((entropy_source, seal))
+}
+
+/// Verifies the block seal H_s and the entropy source H_v in one call
+///
+/// The seal is checked over the unsealed header encoding with the ticket
+/// context and `ticket_attempt` when `sealed_with_ticket` is set, and with the
+/// fallback context otherwise. `ticket_attempt` is ignored in fallback mode.
+///
+/// On success writes Y(H_s) to seal_output_out and Y(H_v) to
+/// entropy_output_out, which must each be BANDERSNATCH_OUTPUT_LENGTH bytes
+///
+/// The public key must be BANDERSNATCH_PUBLIC_LENGTH bytes
+/// The entropy must be 32 bytes
+/// Both signatures must be BANDERSNATCH_SIGNATURE_LENGTH bytes
+/// Returns 0 on success, -1 on invalid input, -2 if the seal does not verify,
+/// -3 if the entropy source does not verify, -4 if the seal cannot be decoded
+/// and -5 if the entropy source cannot be decoded
+#[no_mangle]
+pub unsafe extern "C" fn bandersnatch_verify_block_seal(
+  public_key: *const u8,
+  unsealed_header: *const u8,
+  unsealed_header_len: size_t,
+  entropy: *const u8,
+  sealed_with_ticket: bool,
+  ticket_attempt: u8,
+  seal: *const u8,
+  entropy_source: *const u8,
+  seal_output_out: *mut u8,
+  entropy_output_out: *mut u8,
+) -> c_int {
+  if public_key.is_null()
+    || unsealed_header.is_null()
+    || entropy.is_null()
+    || seal.is_null()
+    || entropy_source.is_null()
+    || seal_output_out.is_null()
+    || entropy_output_out.is_null()
+  {
+    return -1;
+  }
+
+  let public_key = slice::from_raw_parts(public_key, PUBLIC_LENGTH);
+  let unsealed_header =
+    slice::from_raw_parts(unsealed_header, unsealed_header_len);
+  let entropy = &*(entropy as *const [u8; ENTROPY_LENGTH]);
+  let seal = slice::from_raw_parts(seal, SIGNATURE_LENGTH);
+  let entropy_source = slice::from_raw_parts(entropy_source, SIGNATURE_LENGTH);
+
+  let public =
+    if let Ok(p) = Public::deserialize_compressed_unchecked(public_key) {
+      p
+    } else {
+      return -1;
+    };
+
+  let Ok(seal) = BandersnatchSignature::deserialize_compressed_unchecked(seal)
+  else {
+    return -4;
+  };
+  let Ok(entropy_source) =
+    BandersnatchSignature::deserialize_compressed_unchecked(entropy_source)
+  else {
+    return -5;
+  };
+
+  let mode = if sealed_with_ticket {
+    SealMode::Ticket(ticket_attempt)
+  } else {
+    SealMode::Fallback
+  };
+
+  match verify_seal_impl(
+    public,
+    unsealed_header,
+    entropy,
+    mode,
+    seal,
+    entropy_source,
+  ) {
+    Ok((seal_output, entropy_output)) => {
+      ptr::copy_nonoverlapping(
+        seal_output.as_ptr(),
+        seal_output_out,
+        OUTPUT_LENGTH,
+      );
+      ptr::copy_nonoverlapping(
+        entropy_output.as_ptr(),
+        entropy_output_out,
+        OUTPUT_LENGTH,
+      );
+      0
+    }
+    Err(SealError::SealVerificationFailed) => -2,
+    Err(SealError::EntropySourceVerificationFailed) => -3,
+  }
+}
+
+/// Verifies only the block seal H_s, see `bandersnatch_verify_block_seal`
+///
+/// Lets the seal and the entropy source be checked in parallel, the entropy
+/// source input only needs Y(H_s), which can be read from the seal without
+/// verifying it.
+///
+/// On success writes Y(H_s) to seal_output_out which must be
+/// BANDERSNATCH_OUTPUT_LENGTH bytes
+/// Returns 0 on success, -1 on invalid input, -2 if the seal does not verify
+/// and -4 if the seal cannot be decoded
+#[no_mangle]
+pub unsafe extern "C" fn bandersnatch_verify_seal(
+  public_key: *const u8,
+  unsealed_header: *const u8,
+  unsealed_header_len: size_t,
+  entropy: *const u8,
+  sealed_with_ticket: bool,
+  ticket_attempt: u8,
+  seal: *const u8,
+  seal_output_out: *mut u8,
+) -> c_int {
+  if public_key.is_null()
+    || unsealed_header.is_null()
+    || entropy.is_null()
+    || seal.is_null()
+    || seal_output_out.is_null()
+  {
+    return -1;
+  }
+
+  let public_key = slice::from_raw_parts(public_key, PUBLIC_LENGTH);
+  let unsealed_header =
+    slice::from_raw_parts(unsealed_header, unsealed_header_len);
+  let entropy = &*(entropy as *const [u8; ENTROPY_LENGTH]);
+  let seal = slice::from_raw_parts(seal, SIGNATURE_LENGTH);
+
+  let Ok(public) = Public::deserialize_compressed_unchecked(public_key) else {
+    return -1;
+  };
+  let Ok(seal) = BandersnatchSignature::deserialize_compressed_unchecked(seal)
+  else {
+    return -4;
+  };
+
+  let mode = if sealed_with_ticket {
+    SealMode::Ticket(ticket_attempt)
+  } else {
+    SealMode::Fallback
+  };
+
+  match verify_seal_signature_impl(public, unsealed_header, entropy, mode, seal)
+  {
+    Ok(seal_output) => {
+      ptr::copy_nonoverlapping(
+        seal_output.as_ptr(),
+        seal_output_out,
+        OUTPUT_LENGTH,
+      );
+      0
+    }
+    Err(_) => -2,
+  }
+}
+
+/// Verifies only the entropy source H_v over `seal_output`, Y(H_s)
+///
+/// On success writes Y(H_v) to entropy_output_out which must be
+/// BANDERSNATCH_OUTPUT_LENGTH bytes
+/// Returns 0 on success, -1 on invalid input, -3 if the entropy source does
+/// not verify and -5 if the entropy source cannot be decoded
+#[no_mangle]
+pub unsafe extern "C" fn bandersnatch_verify_entropy_source(
+  public_key: *const u8,
+  seal_output: *const u8,
+  entropy_source: *const u8,
+  entropy_output_out: *mut u8,
+) -> c_int {
+  if public_key.is_null()
+    || seal_output.is_null()
+    || entropy_source.is_null()
+    || entropy_output_out.is_null()
+  {
+    return -1;
+  }
+
+  let public_key = slice::from_raw_parts(public_key, PUBLIC_LENGTH);
+  let seal_output = &*(seal_output as *const [u8; OUTPUT_LENGTH]);
+  let entropy_source = slice::from_raw_parts(entropy_source, SIGNATURE_LENGTH);
+
+  let Ok(public) = Public::deserialize_compressed_unchecked(public_key) else {
+    return -1;
+  };
+  let Ok(entropy_source) =
+    BandersnatchSignature::deserialize_compressed_unchecked(entropy_source)
+  else {
+    return -5;
+  };
+
+  match verify_entropy_source_impl(public, seal_output, entropy_source) {
+    Ok(entropy_output) => {
+      ptr::copy_nonoverlapping(
+        entropy_output.as_ptr(),
+        entropy_output_out,
+        OUTPUT_LENGTH,
+      );
+      0
+    }
+    Err(_) => -3,
+  }
+}
+
+/// Signs the entropy source H_v and the block seal H_s for block authoring
+///
+/// The unsealed header encoding must contain H_v at `entropy_source_offset`,
+/// whatever is there is replaced by the new entropy source before the header
+/// is sealed. The seal mode is selected as in
+/// `bandersnatch_verify_block_seal`.
+///
+/// Writes H_v to entropy_source_out and H_s to seal_out which must each be
+/// BANDERSNATCH_SIGNATURE_LENGTH bytes
+/// The secret key must be BANDERSNATCH_SECRET_LENGTH bytes
+/// The entropy must be 32 bytes
+/// Returns 0 on success, -1 on error
+#[no_mangle]
+pub unsafe extern "C" fn bandersnatch_sign_block_seal(
+  secret: *const u8,
+  unsealed_header: *const u8,
+  unsealed_header_len: size_t,
+  entropy_source_offset: size_t,
+  entropy: *const u8,
+  sealed_with_ticket: bool,
+  ticket_attempt: u8,
+  entropy_source_out: *mut u8,
+  seal_out: *mut u8,
+) -> c_int {
+  if secret.is_null()
+    || unsealed_header.is_null()
+    || entropy.is_null()
+    || entropy_source_out.is_null()
+    || seal_out.is_null()
+  {
+    return -1;
+  }
+
+  let secret_slice = slice::from_raw_parts(secret, SECRET_LENGTH);
+  let mut unsealed_header =
+    slice::from_raw_parts(unsealed_header, unsealed_header_len).to_vec();
+  let entropy = &*(entropy as *const [u8; ENTROPY_LENGTH]);
+
+  let secret = if let Ok(s) = Secret::deserialize_compressed(secret_slice) {
+    s
+  } else {
+    return -1;
+  };
+
+  let mode = if sealed_with_ticket {
+    SealMode::Ticket(ticket_attempt)
+  } else {
+    SealMode::Fallback
+  };
+
+  let Ok((entropy_source, seal)) = sign_seal_impl(
+    &secret,
+    &mut unsealed_header,
+    entropy_source_offset,
+    entropy,
+    mode,
+  ) else {
+    return -1;
+  };
+
+  let mut signature_buf = [0u8; SIGNATURE_LENGTH];
+  for (signature, out) in
+    [(entropy_source, entropy_source_out), (seal, seal_out)]
+  {
+    if signature
+      .serialize_compressed(&mut signature_buf[..])
+      .is_err()
+    {
+      return -1;
+    }
+    ptr::copy_nonoverlapping(signature_buf.as_ptr(), out, SIGNATURE_LENGTH);
+  }
+
+  0
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_verify_block_seal() {
+    let secret = Secret::from_seed(&42_usize.to_le_bytes());
+    let header = "unsealed header".as_bytes();
+    let entropy = [7u8; ENTROPY_LENGTH];
+    let mode = SealMode::Ticket(1);
+
+    let seal = bandersnatch_sign_impl(
+      secret.clone(),
+      &seal_vrf_input(&entropy, mode),
+      header,
+    );
+    let seal_output = seal.output.hash();
+    let entropy_source = bandersnatch_sign_impl(
+      secret.clone(),
+      &[ENTROPY_CONTEXT, &seal_output[..OUTPUT_LENGTH]].concat(),
+      &[],
+    );
+
+    let (verified_seal_output, _) = verify_seal_impl(
+      secret.public(),
+      header,
+      &entropy,
+      mode,
+      seal.clone(),
+      entropy_source.clone(),
+    )
+    .unwrap();
+    assert_eq!(verified_seal_output[..], seal_output[..OUTPUT_LENGTH]);
+
+    // The attempt index is part of the seal context
+    assert_eq!(
+      verify_seal_impl(
+        secret.public(),
+        header,
+        &entropy,
+        SealMode::Ticket(0),
+        seal.clone(),
+        entropy_source.clone(),
+      ),
+      Err(SealError::SealVerificationFailed)
+    );
+
+    // The entropy source must be over the seal output, not the seal input
+    assert_eq!(
+      verify_seal_impl(
+        secret.public(),
+        header,
+        &entropy,
+        mode,
+        seal.clone(),
+        seal,
+      ),
+      Err(SealError::EntropySourceVerificationFailed)
+    );
+  }
+
+  #[test]
+  fn test_signed_block_seal_verifies() {
+    let secret = Secret::from_seed(&42_usize.to_le_bytes());
+    let entropy = [7u8; ENTROPY_LENGTH];
+    // Header fields around a placeholder for H_v
+    let mut header = vec![1u8; 16];
+    header.extend([0u8; SIGNATURE_LENGTH]);
+    header.extend([2u8; 4]);
+
+    for mode in [SealMode::Ticket(1), SealMode::Fallback] {
+      let mut unsealed_header = header.clone();
+      let (entropy_source, seal) =
+        sign_seal_impl(&secret, &mut unsealed_header, 16, &entropy, mode)
+          .unwrap();
+
+      let mut entropy_source_bytes = [0u8; SIGNATURE_LENGTH];
+      entropy_source
+        .serialize_compressed(&mut entropy_source_bytes[..])
+        .unwrap();
+      assert_eq!(
+        unsealed_header[16..16 + SIGNATURE_LENGTH],
+        entropy_source_bytes
+      );
+
+      verify_seal_impl(
+        secret.public(),
+        &unsealed_header,
+        &entropy,
+        mode,
+        seal,
+        entropy_source,
+      )
+      .unwrap();
+    }
+
+    // H_v has to fit inside the header
+    assert!(sign_seal_impl(
+      &secret,
+      &mut header,
+      17 + 4,
+      &entropy,
+      SealMode::Fallback
+    )
+    .is_err());
+  }
+}
@@ -7,16 +7,16 @@ use std::slice;
 
 // Constants defined according to section G of the whitepaper
 // "The singly-contextualized Bandersnatch Schnorr-like signatures"
-const SECRET_LENGTH: usize = 32; // Secret key length in bytes
-const PUBLIC_LENGTH: usize = 32; // Public key length in bytes
-const SIGNATURE_LENGTH: usize = 96; // F_m_k<c> ⊂ Y_96 signature length from equation G.1
-const OUTPUT_LENGTH: usize = 32; // VRF output hash length Y(s ∈ F_m_k<c>) ∈ H from equation G.2
+pub(crate) const SECRET_LENGTH: usize = 32; // Secret key length in bytes
+pub(crate) const PUBLIC_LENGTH: usize = 32; // Public key length in bytes
+pub(crate) const SIGNATURE_LENGTH: usize = 96; // F_m_k<c> ⊂ Y_96 signature length from equation G.1
+pub(crate) const OUTPUT_LENGTH: usize = 32; // VRF output hash length Y(s ∈ F_m_k<c>) ∈ H from equation G.2
 
 /// Represents a complete VRF signature according to equation G.1 in the whitepaper
 #[derive(CanonicalSerialize, CanonicalDeserialize, Clone)]
 pub struct BandersnatchSignature {
-  output: Output,   // VRF output component
-  proof: IetfProof, // Proof component
+  pub(crate) output: Output,   // VRF output component
+  pub(crate) proof: IetfProof, // Proof component
 }
 
 /// Creates the VRF input point from input data
@@ -29,7 +29,7 @@ fn create_vrf_input(input_data: &[u8]) -> Input {
 ///
 /// Used for ticket claiming during block production
 /// Only vrf_input_data affects the VRF output according to equation G.2
-fn bandersnatch_sign_impl(
+pub(crate) fn bandersnatch_sign_impl(
   secret: Secret,
   vrf_input_data: &[u8],
   context_data: &[u8],
@@ -43,11 +43,26 @@ fn bandersnatch_sign_impl(
   BandersnatchSignature { output, proof }
 }
 
+/// Evaluates the VRF output hash Y according to equation G.2 without proving
+///
+/// Only the input point depends on the data, so this matches the hash returned
+/// by verifying any signature of `secret` over the same `vrf_input_data`
+pub(crate) fn bandersnatch_vrf_output_impl(
+  secret: &Secret,
+  vrf_input_data: &[u8],
+) -> [u8; OUTPUT_LENGTH] {
+  let output = secret.output(create_vrf_input(vrf_input_data));
+
+  let mut vrf_output_hash = [0u8; OUTPUT_LENGTH];
+  vrf_output_hash.copy_from_slice(&output.hash()[..OUTPUT_LENGTH]);
+  vrf_output_hash
+}
+
 /// Verifies a non-anonymous VRF signature according to equation G.1
 ///
 /// Used for ticket claim verification during block import
 /// Returns the VRF output hash Y(s) as defined in equation G.2 on success
-fn bandersnatch_verify_impl(
+pub(crate) fn bandersnatch_verify_impl(
   public: Public,
   vrf_input_data: &[u8],
   context_data: &[u8],
@@ -180,6 +195,45 @@ pub unsafe extern "C" fn bandersnatch_sign(
   0
 }
 
+/// Computes the VRF output hash Y(s) defined in equation G.2 without creating
+/// a proof
+///
+/// Used where only the output matters, such as predicting our own ticket ids
+/// or checking whether we author a fallback slot
+///
+/// Writes the output hash to output_hash_out which must be BANDERSNATCH_OUTPUT_LENGTH bytes
+/// The secret key must be BANDERSNATCH_SECRET_LENGTH bytes
+/// Returns 0 on success, -1 on error
+#[no_mangle]
+pub unsafe extern "C" fn bandersnatch_vrf_output(
+  secret: *const u8,
+  vrf_input_data: *const u8,
+  vrf_input_len: size_t,
+  output_hash_out: *mut u8,
+) -> c_int {
+  if secret.is_null() || vrf_input_data.is_null() || output_hash_out.is_null() {
+    return -1;
+  }
+
+  let secret_slice = slice::from_raw_parts(secret, SECRET_LENGTH);
+  let vrf_input = slice::from_raw_parts(vrf_input_data, vrf_input_len);
+
+  let secret = if let Ok(s) = Secret::deserialize_compressed(secret_slice) {
+    s
+  } else {
+    return -1;
+  };
+
+  let output_hash = bandersnatch_vrf_output_impl(&secret, vrf_input);
+  ptr::copy_nonoverlapping(
+    output_hash.as_ptr(),
+    output_hash_out,
+    OUTPUT_LENGTH,
+  );
+
+  0
+}
+
 /// Verifies a VRF signature according to equation G.1
 ///
 /// On success, writes the VRF output hash Y(s) defined in equation G.2 to output_hash_out
@@ -278,6 +332,7 @@ pub unsafe extern "C" fn bandersnatch_output_hash(
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::ring_vrf::verifier::Verifier;
 
   #[test]
   fn test_bandersnatch_signatures() {
@@ -299,4 +354,32 @@ mod tests {
     )
     .unwrap();
   }
+
+  #[test]
+  fn test_vrf_output_matches_verified_output() {
+    let secret = Secret::from_seed(&42_usize.to_le_bytes());
+
+    let signature = bandersnatch_sign_impl(
+      secret.clone(),
+      "message".as_bytes(),
+      "context".as_bytes(),
+    );
+    let mut encoded = Vec::new();
+    signature.serialize_compressed(&mut encoded).unwrap();
+    let verified = bandersnatch_verify_impl(
+      secret.public(),
+      "message".as_bytes(),
+      "context".as_bytes(),
+      signature,
+    )
+    .unwrap();
+    let ietf_verified = Verifier::new(vec![secret.public()])
+      .unwrap()
+      .ietf_vrf_verify("message".as_bytes(), "context".as_bytes(), &encoded, 0)
+      .unwrap();
+
+    let output = bandersnatch_vrf_output_impl(&secret, "message".as_bytes());
+    assert_eq!(output, verified);
+    assert_eq!(output, ietf_verified);
+  }
 }
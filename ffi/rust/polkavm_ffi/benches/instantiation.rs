@@ -0,0 +1,52 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use polkavm_ffi::{module_cache, ProgramExecutor};
+
+/// Builds a standard program blob whose code is `instruction_count`
+/// fallthrough instructions followed by a trap
+fn standard_program(instruction_count: usize) -> Vec<u8> {
+  let mut code = vec![1u8; instruction_count];
+  code.push(0);
+
+  let mut deblob = vec![0, 0];
+  deblob.push(0xc0 | (code.len() >> 16) as u8);
+  deblob.extend_from_slice(&(code.len() as u16).to_le_bytes());
+  deblob.extend_from_slice(&code);
+  deblob.extend(vec![0xff; code.len().div_ceil(8)]);
+
+  let mut program = Vec::new();
+  program.extend_from_slice(&[0, 0, 0]); // ro data size
+  program.extend_from_slice(&[0, 0, 0]); // rw data size
+  program.extend_from_slice(&[1, 0]); // heap pages
+  program.extend_from_slice(&[0, 0x10, 0]); // stack size
+  program.extend_from_slice(&(deblob.len() as u32).to_le_bytes());
+  program.extend_from_slice(&deblob);
+  program
+}
+
+fn instantiation(c: &mut Criterion) {
+  let program = standard_program(16 * 1024);
+  let args = [0u8; 64];
+
+  let mut group = c.benchmark_group("instantiation");
+  group.throughput(Throughput::Elements(1));
+
+  group.bench_function("cached_module", |b| {
+    b.iter(|| {
+      ProgramExecutor::from_standard_program(&program, &args, 1_000_000)
+        .unwrap()
+    })
+  });
+
+  group.bench_function("cold_module", |b| {
+    b.iter(|| {
+      module_cache::clear();
+      ProgramExecutor::from_standard_program(&program, &args, 1_000_000)
+        .unwrap()
+    })
+  });
+
+  group.finish();
+}
+
+criterion_group!(benches, instantiation);
+criterion_main!(benches);
@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use polkavm_ffi::differential::{check, FuzzProgram};
+
+fuzz_target!(|program: FuzzProgram| {
+  if let Err(divergence) = check(&program) {
+    panic!("{divergence}");
+  }
+});
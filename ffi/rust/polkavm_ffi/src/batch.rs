@@ -0,0 +1,298 @@
+//! Runs independent executions on a pool of worker threads
+//!
+//! Each job creates its own executor and runs it to completion on whichever
+//! worker picks it up. Modules are shared through the module cache, so a
+//! batch over the same blob compiles it once.
+
+use std::{
+  num::NonZeroUsize,
+  panic::{self, AssertUnwindSafe},
+  ptr, slice,
+  sync::atomic::{AtomicUsize, Ordering},
+  thread,
+  time::Duration,
+};
+
+use crate::{
+  memory_regions, ExecutionResult, InitializationError, MemoryPage,
+  MemoryRegion, ProgramExecutor,
+};
+
+/// Inputs of one execution, as passed to `create_executor`, and its run
+/// limits as passed to `executor_set_run_limits`, 0 for unlimited
+#[repr(C)]
+#[derive(Debug, Clone)]
+pub struct BatchJob {
+  bytecode: *const u8,
+  bytecode_len: usize,
+  initial_pages: *const MemoryPage,
+  page_count: usize,
+  initial_registers: *const u64,
+  gas_limit: u64,
+  max_steps: u64,
+  max_millis: u64,
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum BatchError {
+  /// The job panicked, numbered after the `InitializationError` codes
+  JobPanicked = 7,
+}
+
+/// Why a job has no executor
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum JobError {
+  Initialization(InitializationError),
+  Panicked,
+}
+
+impl JobError {
+  fn code(self) -> i32 {
+    match self {
+      JobError::Initialization(err) => err as i32,
+      JobError::Panicked => BatchError::JobPanicked as i32,
+    }
+  }
+}
+
+/// Executors of a finished batch, in job order
+pub struct ExecutorBatch {
+  executors: Vec<Result<ProgramExecutor, JobError>>,
+}
+
+pub(crate) struct Job<'a> {
+  pub(crate) bytecode: &'a [u8],
+  pub(crate) regions: Vec<(MemoryRegion, &'a [u8])>,
+  pub(crate) registers: [u64; 13],
+  pub(crate) gas_limit: u64,
+  pub(crate) max_steps: Option<u64>,
+  pub(crate) max_duration: Option<Duration>,
+}
+
+impl Job<'_> {
+  fn run(&self) -> Result<ProgramExecutor, JobError> {
+    let mut executor = ProgramExecutor::from_parts(
+      self.bytecode,
+      &self.regions,
+      self.registers,
+      self.gas_limit,
+    )
+    .map_err(JobError::Initialization)?;
+    executor.set_step_limit(self.max_steps);
+    executor.set_time_limit(self.max_duration);
+    executor.run_to_completion();
+    Ok(executor)
+  }
+}
+
+/// Runs `jobs` on up to `threads` workers, all available cores when 0
+///
+/// A panicking job is reported as `JobError::Panicked` and does not take
+/// the rest of the batch down.
+pub(crate) fn run_jobs(
+  jobs: &[Job],
+  threads: usize,
+) -> Vec<Result<ProgramExecutor, JobError>> {
+  let threads = match threads {
+    0 => thread::available_parallelism().map_or(1, NonZeroUsize::get),
+    threads => threads,
+  }
+  .min(jobs.len());
+
+  let next = AtomicUsize::new(0);
+  let mut executors: Vec<_> = jobs.iter().map(|_| None).collect();
+  thread::scope(|scope| {
+    let workers: Vec<_> = (0..threads)
+      .map(|_| {
+        scope.spawn(|| {
+          let mut done = Vec::new();
+          loop {
+            let i = next.fetch_add(1, Ordering::Relaxed);
+            let Some(job) = jobs.get(i) else {
+              return done;
+            };
+            let executor = panic::catch_unwind(AssertUnwindSafe(|| job.run()))
+              .unwrap_or(Err(JobError::Panicked));
+            done.push((i, executor));
+          }
+        })
+      })
+      .collect();
+    for worker in workers {
+      for (i, executor) in worker.join().unwrap_or_default() {
+        executors[i] = Some(executor);
+      }
+    }
+  });
+  executors
+    .into_iter()
+    .map(|executor| executor.unwrap_or(Err(JobError::Panicked)))
+    .collect()
+}
+
+/// Runs `job_count` jobs to completion on `threads` worker threads, or on
+/// all available cores when `threads` is 0
+///
+/// A job stopped by its run limits has an `Interrupted` result, its executor
+/// can be continued through `executor_batch_get`.
+///
+/// For every job, `errors_out` receives 0 and `results_out` its final result
+/// if the executor could be created, otherwise `errors_out` receives the
+/// `InitializationError` code, or `BatchError::JobPanicked` if the job
+/// panicked, and the result is left untouched. The results borrow memory
+/// from the returned batch.
+///
+/// # Safety
+///
+/// This function is unsafe because it:
+/// - Accepts raw pointers as input
+/// - Returns pages borrowed from the batch, they must not be used after the
+///   batch is freed
+#[no_mangle]
+pub unsafe extern "C" fn run_executor_batch(
+  jobs: *const BatchJob,
+  job_count: usize,
+  threads: usize,
+  results_out: *mut ExecutionResult,
+  errors_out: *mut i32,
+) -> *mut ExecutorBatch {
+  let jobs = if job_count == 0 {
+    &[][..]
+  } else {
+    slice::from_raw_parts(jobs, job_count)
+  };
+  let jobs: Vec<Job> = jobs
+    .iter()
+    .map(|job| {
+      let mut registers = [0u64; 13];
+      registers
+        .copy_from_slice(slice::from_raw_parts(job.initial_registers, 13));
+      Job {
+        bytecode: slice::from_raw_parts(job.bytecode, job.bytecode_len),
+        regions: memory_regions(job.initial_pages, job.page_count),
+        registers,
+        gas_limit: job.gas_limit,
+        max_steps: (job.max_steps != 0).then_some(job.max_steps),
+        max_duration: (job.max_millis != 0)
+          .then(|| Duration::from_millis(job.max_millis)),
+      }
+    })
+    .collect();
+
+  let mut batch = ExecutorBatch {
+    executors: run_jobs(&jobs, threads),
+  };
+  for (i, executor) in batch.executors.iter_mut().enumerate() {
+    match executor {
+      Ok(executor) => {
+        *errors_out.add(i) = 0;
+        *results_out.add(i) = executor.create_execution_result();
+      }
+      Err(err) => *errors_out.add(i) = err.code(),
+    }
+  }
+  Box::into_raw(Box::new(batch))
+}
+
+/// Returns the executor of job `index`, or null if it could not be created
+/// or panicked
+///
+/// The executor stays owned by the batch and can be inspected or continued
+/// until the batch is freed.
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts a raw pointer as input
+#[no_mangle]
+pub unsafe extern "C" fn executor_batch_get(
+  batch: *mut ExecutorBatch,
+  index: usize,
+) -> *mut ProgramExecutor {
+  match (*batch).executors.get_mut(index) {
+    Some(Ok(executor)) => executor,
+    _ => ptr::null_mut(),
+  }
+}
+
+/// Frees a batch along with all of its executors
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts a raw pointer as input
+#[no_mangle]
+pub unsafe extern "C" fn free_executor_batch(batch: *mut ExecutorBatch) {
+  if !batch.is_null() {
+    drop(Box::from_raw(batch));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use polkavm::Reg;
+  use polkavm_common::{program::asm, writer::ProgramBlobBuilder};
+
+  use super::*;
+  use crate::ExecutionStatus;
+
+  fn assert_send<T: Send>() {}
+
+  #[test]
+  fn test_run_jobs_keeps_job_order() {
+    assert_send::<ProgramExecutor>();
+
+    let mut builder = ProgramBlobBuilder::new_64bit();
+    builder.add_export_by_basic_block(0, b"main");
+    builder.set_code(&[asm::add_64(Reg::A2, Reg::A0, Reg::A1)], &[]);
+    let program = builder.into_vec();
+
+    let jobs: Vec<Job> = (0..16)
+      .map(|i| {
+        let mut registers = [0u64; 13];
+        registers[Reg::A0 as usize] = i;
+        registers[Reg::A1 as usize] = 100;
+        Job {
+          bytecode: &program,
+          regions: Vec::new(),
+          registers,
+          gas_limit: 10000,
+          max_steps: None,
+          max_duration: None,
+        }
+      })
+      .collect();
+
+    let executors = run_jobs(&jobs, 4);
+    assert_eq!(executors.len(), jobs.len());
+    for (i, executor) in executors.iter().enumerate() {
+      let executor = executor.as_ref().unwrap();
+      assert!(executor.is_finished());
+      assert_eq!(executor.register(Reg::A2 as u32), Ok(100 + i as u64));
+    }
+  }
+  #[test]
+  fn test_run_jobs_applies_run_limits() {
+    let mut builder = ProgramBlobBuilder::new();
+    builder.add_export_by_basic_block(0, b"main");
+    builder.set_code(&[asm::jump(0)], &[]);
+    let program = builder.into_vec();
+
+    let job = |max_steps, max_duration| Job {
+      bytecode: &program,
+      regions: Vec::new(),
+      registers: [0; 13],
+      gas_limit: u64::MAX >> 1,
+      max_steps,
+      max_duration,
+    };
+    let jobs = [
+      job(Some(100), None),
+      job(None, Some(Duration::from_millis(10))),
+    ];
+
+    for executor in run_jobs(&jobs, 2) {
+      let executor = executor.unwrap();
+      assert_eq!(executor.current_status, ExecutionStatus::Interrupted);
+    }
+  }
+}
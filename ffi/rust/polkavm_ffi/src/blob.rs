@@ -0,0 +1,202 @@
+//! Conversion of JAM program blobs into polkavm program containers
+//!
+//! JAM programs use the deblob format `E(|j|) ‖ E_1(z) ‖ E(|c|) ‖ E_z(j) ‖ c
+//! ‖ k` from the Gray Paper, polkavm expects its own container with the same
+//! code, bitmask and jump table wrapped in sections.
+
+const BLOB_MAGIC: &[u8; 4] = b"PVM\0";
+const BLOB_VERSION_64_BIT: u8 = 0;
+
+const SECTION_MEMORY_CONFIG: u8 = 1;
+const SECTION_RO_DATA: u8 = 2;
+const SECTION_RW_DATA: u8 = 3;
+const SECTION_CODE_AND_JUMP_TABLE: u8 = 6;
+const SECTION_END_OF_FILE: u8 = 0;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum BlobError {
+  UnexpectedEnd,
+  InvalidJumpTableItemLength,
+  InvalidLength,
+}
+
+/// Decodes a natural number using the general integer codec (Gray Paper C.6)
+pub fn decode_natural(
+  bytes: &[u8],
+  index: &mut usize,
+) -> Result<u64, BlobError> {
+  let prefix = *bytes.get(*index).ok_or(BlobError::UnexpectedEnd)?;
+  *index += 1;
+
+  let extra_bytes = prefix.leading_ones() as usize;
+  let tail = bytes
+    .get(*index..*index + extra_bytes)
+    .ok_or(BlobError::UnexpectedEnd)?;
+  *index += extra_bytes;
+
+  let mut value = 0u64;
+  for (i, &byte) in tail.iter().enumerate() {
+    value |= (byte as u64) << (8 * i);
+  }
+
+  if extra_bytes < 8 {
+    let high_bits = (prefix as u64) & (0xff >> (extra_bytes + 1));
+    value |= high_bits << (8 * extra_bytes);
+  }
+
+  Ok(value)
+}
+
+/// A program in the JAM deblob format
+#[derive(Debug)]
+pub struct JamProgram<'a> {
+  pub jump_table_item_length: u8,
+  pub jump_table: &'a [u8],
+  pub code: &'a [u8],
+  pub bitmask: &'a [u8],
+}
+
+impl<'a> JamProgram<'a> {
+  pub fn parse(raw: &'a [u8]) -> Result<Self, BlobError> {
+    let mut index = 0;
+    let jump_table_length = decode_natural(raw, &mut index)? as usize;
+    let jump_table_item_length =
+      *raw.get(index).ok_or(BlobError::UnexpectedEnd)?;
+    index += 1;
+    if jump_table_item_length > 4
+      || (jump_table_length > 0 && jump_table_item_length == 0)
+    {
+      return Err(BlobError::InvalidJumpTableItemLength);
+    }
+
+    let code_length = decode_natural(raw, &mut index)? as usize;
+    let jump_table_size = jump_table_length
+      .checked_mul(jump_table_item_length as usize)
+      .ok_or(BlobError::InvalidLength)?;
+    let bitmask_size = code_length.div_ceil(8);
+
+    let mut take = |size: usize| -> Result<&'a [u8], BlobError> {
+      let end = index.checked_add(size).ok_or(BlobError::InvalidLength)?;
+      let part = raw.get(index..end).ok_or(BlobError::UnexpectedEnd)?;
+      index = end;
+      Ok(part)
+    };
+
+    Ok(Self {
+      jump_table_item_length,
+      jump_table: take(jump_table_size)?,
+      code: take(code_length)?,
+      // Trailing bytes after the bitmask are tolerated, as in our decoder
+      bitmask: take(bitmask_size)?,
+    })
+  }
+
+  pub fn jump_table_entries(&self) -> impl Iterator<Item = u32> + '_ {
+    let item_length = self.jump_table_item_length.max(1) as usize;
+    self.jump_table.chunks(item_length).map(|chunk| {
+      chunk
+        .iter()
+        .rev()
+        .fold(0u32, |acc, &byte| (acc << 8) | byte as u32)
+    })
+  }
+
+  /// Wraps the program into a polkavm container
+  ///
+  /// The memory config is left empty, memory is laid out by the caller
+  /// through dynamic paging.
+  pub fn to_polkavm_blob(&self) -> Vec<u8> {
+    build_polkavm_blob(self.code, self.bitmask, self.jump_table_entries())
+  }
+}
+
+/// Builds a 64-bit polkavm container, mirroring the Zig `ProgramBuilder`
+pub fn build_polkavm_blob(
+  code: &[u8],
+  bitmask: &[u8],
+  jump_table: impl Iterator<Item = u32>,
+) -> Vec<u8> {
+  let jump_table: Vec<u32> = jump_table.collect();
+
+  let mut blob = Vec::new();
+  blob.extend_from_slice(BLOB_MAGIC);
+  blob.push(BLOB_VERSION_64_BIT);
+  let length_position = blob.len();
+  blob.extend_from_slice(&[0u8; 8]);
+
+  let mut memory_config = Vec::new();
+  write_varint(&mut memory_config, 0); // ro data size
+  write_varint(&mut memory_config, 0); // rw data size
+  write_varint(&mut memory_config, 0); // stack size
+  write_section(&mut blob, SECTION_MEMORY_CONFIG, &memory_config);
+  write_section(&mut blob, SECTION_RO_DATA, &[]);
+  write_section(&mut blob, SECTION_RW_DATA, &[]);
+
+  let mut code_section = Vec::new();
+  write_varint(&mut code_section, jump_table.len() as u32);
+  code_section.push(4); // jump table entry size
+  write_varint(&mut code_section, code.len() as u32);
+  for entry in &jump_table {
+    code_section.extend_from_slice(&entry.to_le_bytes());
+  }
+  code_section.extend_from_slice(code);
+  code_section.extend_from_slice(bitmask);
+  write_section(&mut blob, SECTION_CODE_AND_JUMP_TABLE, &code_section);
+
+  blob.push(SECTION_END_OF_FILE);
+
+  let total_length = blob.len() as u64;
+  blob[length_position..length_position + 8]
+    .copy_from_slice(&total_length.to_le_bytes());
+
+  blob
+}
+
+fn write_section(blob: &mut Vec<u8>, section: u8, data: &[u8]) {
+  blob.push(section);
+  write_varint(blob, data.len() as u32);
+  blob.extend_from_slice(data);
+}
+
+/// Writes a polkavm varint
+fn write_varint(buffer: &mut Vec<u8>, value: u32) {
+  let bits_required = 32 - value.leading_zeros();
+  let x = bits_required >> 3;
+  let length = ((x + bits_required) ^ x) >> 3;
+  let bytes = value.to_le_bytes();
+
+  match length {
+    0 => buffer.push(value as u8),
+    4 => {
+      buffer.push(0b11110000);
+      buffer.extend_from_slice(&bytes);
+    }
+    _ => {
+      let marker = !(0xffu8 >> length);
+      buffer.push(marker | (value >> (8 * length)) as u8);
+      buffer.extend_from_slice(&bytes[..length as usize]);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_jam_program_round_trip() {
+    // The `inst_add_32` program from the PVM test vectors
+    let raw = [0, 0, 3, 190, 135, 9, 1];
+    let program = JamProgram::parse(&raw).unwrap();
+    assert_eq!(program.code, &[190, 135, 9]);
+    assert_eq!(program.bitmask, &[1]);
+
+    let blob = polkavm::ProgramBlob::parse(program.to_polkavm_blob().into())
+      .expect("Failed to parse converted blob");
+    assert_eq!(blob.code(), &[190, 135, 9]);
+
+    let mut index = 0;
+    assert_eq!(decode_natural(&[0x81, 0x02], &mut index), Ok(0x102));
+    assert_eq!(index, 2);
+  }
+}
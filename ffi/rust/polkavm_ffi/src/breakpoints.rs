@@ -0,0 +1,186 @@
+//! Breakpoints for the reference executor
+//!
+//! Stepping from Zig costs a full FFI round trip with a memory dump per
+//! instruction, running up to a breakpoint stays on the Rust side.
+
+use crate::{ExecutionResult, ProgramExecutor};
+
+impl ProgramExecutor {
+  /// Stops execution before the instruction at `pc` is executed
+  pub fn set_breakpoint(&mut self, pc: u32) {
+    self.breakpoints.insert(pc);
+  }
+
+  /// Returns true if a breakpoint was set at `pc`
+  pub fn clear_breakpoint(&mut self, pc: u32) -> bool {
+    self.breakpoints.remove(&pc)
+  }
+
+  pub fn clear_breakpoints(&mut self) {
+    self.breakpoints.clear();
+  }
+
+  /// Returns true if the executor is stopped right before a breakpoint
+  pub fn is_at_breakpoint(&self) -> bool {
+    !self.is_finished()
+      && self
+        .step_pc
+        .is_some_and(|pc| self.breakpoints.contains(&pc))
+  }
+
+  /// Steps until a breakpoint is hit, the program finishes or a run limit
+  /// is reached
+  ///
+  /// At least one step is taken, so calling this again while stopped on a
+  /// breakpoint continues past it.
+  pub fn run_until_breakpoint(&mut self) -> ExecutionResult {
+    self.run_until(|executor| executor.is_at_breakpoint())
+  }
+
+  /// Steps until the instruction at `pc` is next or the program finishes,
+  /// breakpoints on the way are ignored
+  pub fn run_until_pc(&mut self, pc: u32) -> ExecutionResult {
+    self.run_until(|executor| {
+      !executor.is_finished() && executor.step_pc == Some(pc)
+    })
+  }
+
+  fn run_until(
+    &mut self,
+    should_stop: impl Fn(&ProgramExecutor) -> bool,
+  ) -> ExecutionResult {
+    self.run_until_stopped(should_stop);
+    self.create_execution_result()
+  }
+}
+
+/// Sets a breakpoint on the instruction at `pc`
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts a raw pointer as input
+#[no_mangle]
+pub unsafe extern "C" fn executor_set_breakpoint(
+  executor: *mut ProgramExecutor,
+  pc: u32,
+) {
+  (&mut *executor).set_breakpoint(pc);
+}
+
+/// Clears the breakpoint at `pc`, returns false if none was set
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts a raw pointer as input
+#[no_mangle]
+pub unsafe extern "C" fn executor_clear_breakpoint(
+  executor: *mut ProgramExecutor,
+  pc: u32,
+) -> bool {
+  (&mut *executor).clear_breakpoint(pc)
+}
+
+/// Clears all breakpoints
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts a raw pointer as input
+#[no_mangle]
+pub unsafe extern "C" fn executor_clear_breakpoints(
+  executor: *mut ProgramExecutor,
+) {
+  (&mut *executor).clear_breakpoints();
+}
+
+/// Runs until a breakpoint is hit, a fault happens or the program finishes
+///
+/// A breakpoint hit is reported with `Running` status and `final_pc` set to
+/// the breakpoint.
+///
+/// # Safety
+///
+/// This function is unsafe because it:
+/// - Accepts a raw pointer as input
+/// - Returns pages borrowed from the executor, they must not be used after
+///   the next step or after the executor is freed
+#[no_mangle]
+pub unsafe extern "C" fn executor_run_until_breakpoint(
+  executor: *mut ProgramExecutor,
+) -> ExecutionResult {
+  (&mut *executor).run_until_breakpoint()
+}
+
+/// Runs until the instruction at `pc` is next, a fault happens or the
+/// program finishes
+///
+/// # Safety
+///
+/// This function is unsafe because it:
+/// - Accepts a raw pointer as input
+/// - Returns pages borrowed from the executor, they must not be used after
+///   the next step or after the executor is freed
+#[no_mangle]
+pub unsafe extern "C" fn executor_run_until_pc(
+  executor: *mut ProgramExecutor,
+  pc: u32,
+) -> ExecutionResult {
+  (&mut *executor).run_until_pc(pc)
+}
+
+#[cfg(test)]
+mod tests {
+  use polkavm::Reg;
+  use polkavm_common::{program::asm, writer::ProgramBlobBuilder};
+
+  use super::*;
+  use crate::{ExecutionStatus, MemoryPage};
+
+  #[test]
+  fn test_run_until_breakpoint() {
+    let mut builder = ProgramBlobBuilder::new();
+    builder.add_export_by_basic_block(0, b"main");
+    builder.set_code(
+      &[
+        asm::load_imm(Reg::T0, 1),
+        asm::fallthrough(),
+        asm::load_imm(Reg::T1, 2),
+        asm::fallthrough(),
+        asm::add_32(Reg::T2, Reg::T0, Reg::T1),
+        asm::ret(),
+      ],
+      &[],
+    );
+    let program = builder.into_vec();
+    let registers = [0u64; 13];
+    let pages: [MemoryPage; 0] = [];
+
+    unsafe {
+      let mut executor = ProgramExecutor::new(
+        program.as_ptr(),
+        program.len(),
+        pages.as_ptr(),
+        0,
+        registers.as_ptr(),
+        10000,
+      )
+      .expect("Failed to create executor");
+
+      let blocks = executor.instructions.basic_block_starts();
+      assert_eq!(blocks.len(), 3);
+      executor.set_breakpoint(blocks[1]);
+      executor.set_breakpoint(blocks[2]);
+
+      let result = executor.run_until_breakpoint();
+      assert_eq!(result.status, ExecutionStatus::Running);
+      assert_eq!(result.final_pc, blocks[1]);
+      assert_eq!(result.registers[Reg::T0 as usize], 1);
+      assert_eq!(result.registers[Reg::T1 as usize], 0);
+
+      assert!(executor.clear_breakpoint(blocks[2]));
+      assert!(!executor.clear_breakpoint(blocks[2]));
+      let result = executor.run_until_breakpoint();
+      assert_eq!(result.status, ExecutionStatus::Trap);
+      assert_eq!(result.registers[Reg::T2 as usize], 3);
+    }
+  }
+}
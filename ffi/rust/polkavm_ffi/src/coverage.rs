@@ -0,0 +1,336 @@
+//! Instruction coverage collected from reference executions
+//!
+//! Counters are global and accumulate across executors, so the program
+//! generator can see which opcodes and edge cases a whole campaign reached.
+//! Opcodes and edge cases are counted atomically, PCs are counted by each
+//! executor and merged when it is dropped, so workers stepping in parallel
+//! never wait on each other. Collection is off by default.
+
+use std::{
+  collections::BTreeMap,
+  sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Mutex,
+  },
+};
+
+use polkavm::RawInstance;
+use polkavm_common::program::{Instruction, RawReg};
+
+use crate::instructions::memory_access;
+
+/// Operand combinations where implementations commonly disagree
+#[repr(C)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum EdgeCase {
+  /// Division or remainder with a zero divisor
+  DivisionByZero = 0,
+  /// Signed division or remainder of the minimum value by -1
+  DivisionOverflow = 1,
+  /// Shift or rotate by at least the operand width
+  ShiftOverflow = 2,
+  /// Sign extension of a negative value, by an instruction or a signed load
+  SignExtension = 3,
+}
+
+pub const EDGE_CASE_COUNT: usize = 4;
+
+#[repr(C)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct PcCount {
+  pub pc: u32,
+  pub count: u64,
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static OPCODES: [AtomicU64; 256] = [const { AtomicU64::new(0) }; 256];
+static EDGE_CASES: [AtomicU64; EDGE_CASE_COUNT] =
+  [const { AtomicU64::new(0) }; EDGE_CASE_COUNT];
+/// Keyed by PC only, reset between programs to keep it meaningful
+static PCS: Mutex<BTreeMap<u32, u64>> = Mutex::new(BTreeMap::new());
+
+fn pcs() -> std::sync::MutexGuard<'static, BTreeMap<u32, u64>> {
+  // The counters stay consistent even if a holder panicked
+  PCS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn load_counts<const N: usize>(counters: &[AtomicU64; N]) -> [u64; N] {
+  std::array::from_fn(|i| counters[i].load(Ordering::Relaxed))
+}
+
+/// PC counts of one executor, merged into the global counts when dropped
+#[derive(Debug, Default)]
+pub(crate) struct PcCoverage(BTreeMap<u32, u64>);
+
+impl Drop for PcCoverage {
+  fn drop(&mut self) {
+    if self.0.is_empty() {
+      return;
+    }
+    let mut pcs = pcs();
+    for (&pc, &count) in &self.0 {
+      *pcs.entry(pc).or_default() += count;
+    }
+  }
+}
+
+pub fn set_enabled(enabled: bool) {
+  ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+  ENABLED.load(Ordering::Relaxed)
+}
+
+/// Clears the global counts, executors still alive merge their PC counts
+/// afterwards
+pub fn reset() {
+  for counter in OPCODES.iter().chain(&EDGE_CASES) {
+    counter.store(0, Ordering::Relaxed);
+  }
+  pcs().clear();
+}
+
+/// Execution counts indexed by opcode
+pub fn opcode_counts() -> [u64; 256] {
+  load_counts(&OPCODES)
+}
+
+/// Counts indexed by `EdgeCase`
+pub fn edge_case_counts() -> [u64; EDGE_CASE_COUNT] {
+  load_counts(&EDGE_CASES)
+}
+
+/// Execution counts of every reached PC, in ascending PC order, covering
+/// executors that have been dropped
+pub fn pc_counts() -> Vec<PcCount> {
+  pcs()
+    .iter()
+    .map(|(&pc, &count)| PcCount { pc, count })
+    .collect()
+}
+
+/// Counts the instruction at `pc` that is about to be executed
+pub(crate) fn record(
+  pcs: &mut PcCoverage,
+  pc: u32,
+  instruction: &Instruction,
+  instance: &RawInstance,
+) {
+  if !is_enabled() {
+    return;
+  }
+
+  OPCODES[instruction.opcode() as usize].fetch_add(1, Ordering::Relaxed);
+  if let Some(edge_case) = edge_case(instruction, instance) {
+    EDGE_CASES[edge_case as usize].fetch_add(1, Ordering::Relaxed);
+  }
+  *pcs.0.entry(pc).or_default() += 1;
+}
+
+/// Returns the edge case `instruction` hits given the current register values
+fn edge_case(
+  instruction: &Instruction,
+  instance: &RawInstance,
+) -> Option<EdgeCase> {
+  use Instruction::*;
+
+  let reg = |reg: RawReg| instance.reg(reg.get());
+  let division_32 = |s1: RawReg, s2: RawReg, signed: bool| {
+    let (lhs, rhs) = (reg(s1) as u32, reg(s2) as u32);
+    if rhs == 0 {
+      Some(EdgeCase::DivisionByZero)
+    } else if signed && lhs as i32 == i32::MIN && rhs as i32 == -1 {
+      Some(EdgeCase::DivisionOverflow)
+    } else {
+      None
+    }
+  };
+  let division_64 = |s1: RawReg, s2: RawReg, signed: bool| {
+    let (lhs, rhs) = (reg(s1), reg(s2));
+    if rhs == 0 {
+      Some(EdgeCase::DivisionByZero)
+    } else if signed && lhs as i64 == i64::MIN && rhs as i64 == -1 {
+      Some(EdgeCase::DivisionOverflow)
+    } else {
+      None
+    }
+  };
+  let shift = |amount: u64, width: u64| {
+    (amount >= width).then_some(EdgeCase::ShiftOverflow)
+  };
+  let negative = |value: u64, bits: u32| {
+    (value >> (bits - 1) & 1 == 1).then_some(EdgeCase::SignExtension)
+  };
+
+  match *instruction {
+    div_unsigned_32(_, s1, s2) | rem_unsigned_32(_, s1, s2) => {
+      division_32(s1, s2, false)
+    }
+    div_signed_32(_, s1, s2) | rem_signed_32(_, s1, s2) => {
+      division_32(s1, s2, true)
+    }
+    div_unsigned_64(_, s1, s2) | rem_unsigned_64(_, s1, s2) => {
+      division_64(s1, s2, false)
+    }
+    div_signed_64(_, s1, s2) | rem_signed_64(_, s1, s2) => {
+      division_64(s1, s2, true)
+    }
+    shift_logical_left_32(_, _, s2)
+    | shift_logical_right_32(_, _, s2)
+    | shift_arithmetic_right_32(_, _, s2)
+    | rotate_left_32(_, _, s2)
+    | rotate_right_32(_, _, s2) => shift(reg(s2), 32),
+    shift_logical_left_64(_, _, s2)
+    | shift_logical_right_64(_, _, s2)
+    | shift_arithmetic_right_64(_, _, s2)
+    | rotate_left_64(_, _, s2)
+    | rotate_right_64(_, _, s2) => shift(reg(s2), 64),
+    shift_logical_left_imm_alt_32(_, s, _)
+    | shift_logical_right_imm_alt_32(_, s, _)
+    | shift_arithmetic_right_imm_alt_32(_, s, _)
+    | rotate_right_imm_alt_32(_, s, _) => shift(reg(s), 32),
+    shift_logical_left_imm_alt_64(_, s, _)
+    | shift_logical_right_imm_alt_64(_, s, _)
+    | shift_arithmetic_right_imm_alt_64(_, s, _)
+    | rotate_right_imm_alt_64(_, s, _) => shift(reg(s), 64),
+    sign_extend_8(_, s) => negative(reg(s), 8),
+    sign_extend_16(_, s) => negative(reg(s), 16),
+    load_imm(_, imm) => negative(u64::from(imm), 32),
+    load_i8(..)
+    | load_i16(..)
+    | load_i32(..)
+    | load_indirect_i8(..)
+    | load_indirect_i16(..)
+    | load_indirect_i32(..) => {
+      let access = memory_access(instruction, instance)?;
+      let top = access.address.wrapping_add(access.size - 1);
+      negative(u64::from(instance.read_u8(top).ok()?), 8)
+    }
+    _ => None,
+  }
+}
+
+/// Enables or disables coverage collection for all executors
+#[no_mangle]
+pub extern "C" fn coverage_set_enabled(enabled: bool) {
+  set_enabled(enabled);
+}
+
+/// Clears all coverage counters
+#[no_mangle]
+pub extern "C" fn coverage_reset() {
+  reset();
+}
+
+/// Copies the execution count of every opcode into `out`, indexed by opcode
+///
+/// Returns the number of opcodes (256), at most `capacity` counts are
+/// written.
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts raw pointers as input
+#[no_mangle]
+pub unsafe extern "C" fn coverage_opcode_counts(
+  out: *mut u64,
+  capacity: usize,
+) -> usize {
+  copy_counts(&opcode_counts(), out, capacity)
+}
+
+/// Copies the edge case counts into `out`, indexed by `EdgeCase`
+///
+/// Returns the number of edge cases, at most `capacity` counts are written.
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts raw pointers as input
+#[no_mangle]
+pub unsafe extern "C" fn coverage_edge_case_counts(
+  out: *mut u64,
+  capacity: usize,
+) -> usize {
+  copy_counts(&edge_case_counts(), out, capacity)
+}
+
+/// Copies the execution count of every reached PC into `out`
+///
+/// Returns the number of reached PCs, at most `capacity` entries are
+/// written. Call with a capacity of 0 to query the count. Executors add
+/// their PCs when they are freed.
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts raw pointers as input
+#[no_mangle]
+pub unsafe extern "C" fn coverage_pc_counts(
+  out: *mut PcCount,
+  capacity: usize,
+) -> usize {
+  copy_counts(&pc_counts(), out, capacity)
+}
+
+unsafe fn copy_counts<T: Copy>(
+  counts: &[T],
+  out: *mut T,
+  capacity: usize,
+) -> usize {
+  if !out.is_null() {
+    let count = counts.len().min(capacity);
+    std::ptr::copy_nonoverlapping(counts.as_ptr(), out, count);
+  }
+  counts.len()
+}
+
+#[cfg(test)]
+mod tests {
+  use polkavm::Reg;
+  use polkavm_common::{program::asm, writer::ProgramBlobBuilder};
+
+  use super::*;
+  use crate::{MemoryPage, ProgramExecutor};
+
+  #[test]
+  fn test_coverage_counts_edge_cases() {
+    let mut builder = ProgramBlobBuilder::new_64bit();
+    builder.add_export_by_basic_block(0, b"main");
+    builder.set_code(
+      &[
+        asm::load_imm(Reg::T0, 0xffffffff),
+        asm::load_imm(Reg::T1, 64),
+        asm::div_unsigned_64(Reg::T2, Reg::T0, Reg::A0),
+        asm::shift_logical_left_64(Reg::T2, Reg::T0, Reg::T1),
+        asm::ret(),
+      ],
+      &[],
+    );
+    let program = builder.into_vec();
+    let registers = [0u64; 13];
+    let pages: [MemoryPage; 0] = [];
+
+    reset();
+    set_enabled(true);
+    unsafe {
+      let mut executor = ProgramExecutor::new(
+        program.as_ptr(),
+        program.len(),
+        pages.as_ptr(),
+        0,
+        registers.as_ptr(),
+        10000,
+      )
+      .expect("Failed to create executor");
+      executor.run_to_completion();
+    }
+    set_enabled(false);
+
+    // Other tests may run concurrently while collection is enabled
+    let edge_cases = edge_case_counts();
+    assert!(edge_cases[EdgeCase::DivisionByZero as usize] >= 1);
+    assert!(edge_cases[EdgeCase::ShiftOverflow as usize] >= 1);
+    assert!(edge_cases[EdgeCase::SignExtension as usize] >= 1);
+    assert!(opcode_counts().iter().sum::<u64>() >= 5);
+    assert!(pc_counts().iter().any(|entry| entry.pc == 0));
+  }
+}
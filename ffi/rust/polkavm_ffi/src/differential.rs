@@ -0,0 +1,255 @@
+//! Differential execution of random programs on the interpreter and the
+//! compiler backend
+//!
+//! Before our Zig PVM is compared against polkavm, the reference itself has to
+//! agree with itself. Programs are built from `arbitrary` input with
+//! `ProgramBlobBuilder` and must produce the same status, registers, memory
+//! and gas on both backends.
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+use polkavm::Reg;
+use polkavm_common::{
+  program::{asm, Instruction},
+  writer::ProgramBlobBuilder,
+};
+
+use crate::{
+  module_cache::{self, Backend},
+  ExecutionStatus, InitializationError, MemoryRegion, ProgramExecutor,
+};
+
+pub const MEMORY_ADDRESS: u32 = 0x20000;
+pub const MEMORY_SIZE: u32 = 0x1000;
+
+const MAX_INSTRUCTIONS: usize = 64;
+
+/// A random straight-line program with its initial registers and gas
+#[derive(Debug, Clone)]
+pub struct FuzzProgram {
+  pub instructions: Vec<Instruction>,
+  pub registers: [u64; 13],
+  pub gas: u32,
+}
+
+fn arbitrary_reg(u: &mut Unstructured) -> Result<Reg> {
+  Ok(Reg::ALL[u.choose_index(Reg::ALL.len())?])
+}
+
+/// An address inside the memory page with room for an 8 byte access
+fn arbitrary_address(u: &mut Unstructured) -> Result<u32> {
+  Ok(MEMORY_ADDRESS + u.int_in_range(0..=MEMORY_SIZE - 8)?)
+}
+
+fn arbitrary_instruction(u: &mut Unstructured) -> Result<Instruction> {
+  let d = arbitrary_reg(u)?;
+  let s1 = arbitrary_reg(u)?;
+  let s2 = arbitrary_reg(u)?;
+
+  Ok(match u.int_in_range(0..=19)? {
+    0 => asm::load_imm(d, u.arbitrary()?),
+    1 => asm::load_imm64(d, u.arbitrary()?),
+    2 => asm::add_32(d, s1, s2),
+    3 => asm::add_64(d, s1, s2),
+    4 => asm::sub_64(d, s1, s2),
+    5 => asm::mul_64(d, s1, s2),
+    6 => asm::div_unsigned_64(d, s1, s2),
+    7 => asm::div_signed_64(d, s1, s2),
+    8 => asm::rem_unsigned_64(d, s1, s2),
+    9 => asm::rem_signed_64(d, s1, s2),
+    10 => asm::and(d, s1, s2),
+    11 => asm::xor(d, s1, s2),
+    12 => asm::shift_logical_left_64(d, s1, s2),
+    13 => asm::shift_arithmetic_right_64(d, s1, s2),
+    14 => asm::add_imm_64(d, s1, u.arbitrary()?),
+    15 => asm::store_u64(s1, arbitrary_address(u)?),
+    16 => asm::load_i32(d, arbitrary_address(u)?),
+    17 => asm::store_imm_u32(arbitrary_address(u)?, u.arbitrary()?),
+    // Indirect accesses may fault, which both backends must agree on
+    18 => asm::store_indirect_u16(s1, s2, u.arbitrary()?),
+    _ => asm::load_indirect_u64(d, s1, u.arbitrary()?),
+  })
+}
+
+impl<'a> Arbitrary<'a> for FuzzProgram {
+  fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+    let count = u.int_in_range(1..=MAX_INSTRUCTIONS)?;
+    let instructions = (0..count)
+      .map(|_| arbitrary_instruction(u))
+      .collect::<Result<_>>()?;
+
+    Ok(Self {
+      instructions,
+      registers: u.arbitrary()?,
+      gas: u.int_in_range(0..=10_000)?,
+    })
+  }
+}
+
+impl FuzzProgram {
+  pub fn to_blob(&self) -> Vec<u8> {
+    let mut code = self.instructions.clone();
+    code.push(asm::trap());
+
+    let mut builder = ProgramBlobBuilder::new_64bit();
+    builder.add_export_by_basic_block(0, b"main");
+    builder.set_code(&code, &[]);
+    builder.into_vec()
+  }
+}
+
+/// Final state of a run, compared between backends
+#[derive(Debug, PartialEq)]
+pub struct Outcome {
+  pub status: ExecutionStatus,
+  pub registers: [u64; 13],
+  /// The memory page, None if it could not be read back
+  pub memory: Option<Vec<u8>>,
+  pub gas: i64,
+  pub segfault_address: u32,
+}
+
+/// Runs the program to completion on `backend`, failing only if it could
+/// not be instantiated
+pub fn run(
+  program: &FuzzProgram,
+  backend: Backend,
+) -> std::result::Result<Outcome, InitializationError> {
+  let memory = vec![0u8; MEMORY_SIZE as usize];
+  let region = MemoryRegion {
+    address: MEMORY_ADDRESS,
+    size: MEMORY_SIZE as usize,
+    is_writable: true,
+  };
+
+  let mut executor = ProgramExecutor::from_parts_with_backend(
+    &program.to_blob(),
+    &[(region, &memory)],
+    program.registers,
+    program.gas as u64,
+    backend,
+  )?;
+  executor.run_to_completion();
+
+  Ok(Outcome {
+    status: executor.current_status,
+    registers: Reg::ALL.map(|reg| executor.instance.reg(reg)),
+    memory: executor
+      .instance
+      .read_memory(MEMORY_ADDRESS, MEMORY_SIZE)
+      .ok(),
+    gas: executor.gas(),
+    segfault_address: executor.segfault_address,
+  })
+}
+
+/// How a program fared when run on both backends
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Comparison {
+  /// Both backends reached the same outcome
+  Matched,
+  /// The compiler backend is not available on this host, nothing was
+  /// compared
+  Skipped,
+  /// Neither backend could instantiate the program, for the same reason
+  Uninstantiable(InitializationError),
+}
+
+/// Compares the results of both backends, returning a description of the
+/// divergence if they differ in outcome or in why they failed to
+/// instantiate
+fn compare(
+  interpreter: std::result::Result<Outcome, InitializationError>,
+  compiler: std::result::Result<Outcome, InitializationError>,
+) -> std::result::Result<Comparison, String> {
+  match (interpreter, compiler) {
+    (Ok(interpreter), Ok(compiler)) if interpreter == compiler => {
+      Ok(Comparison::Matched)
+    }
+    (Err(interpreter), Err(compiler)) if interpreter == compiler => {
+      Ok(Comparison::Uninstantiable(interpreter))
+    }
+    (interpreter, compiler) => Err(format!(
+      "interpreter: {interpreter:?}\ncompiler: {compiler:?}"
+    )),
+  }
+}
+
+/// Runs the program on both backends and returns both outcomes if they
+/// differ
+pub fn check(program: &FuzzProgram) -> std::result::Result<Comparison, String> {
+  if !module_cache::is_backend_available(Backend::Compiler) {
+    return Ok(Comparison::Skipped);
+  }
+
+  compare(
+    run(program, Backend::Interpreter),
+    run(program, Backend::Compiler),
+  )
+  .map_err(|divergence| {
+    format!(
+      "backends diverge on {:?}\n{divergence}",
+      program.instructions
+    )
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  #[cfg_attr(
+    not(all(target_arch = "x86_64", target_os = "linux")),
+    ignore = "the compiler backend only runs on x86_64 Linux"
+  )]
+  fn test_differential_backends() {
+    // Deterministic xorshift input, the fuzz target explores the rest
+    let mut state = 0x2545f4914f6cdd1du64;
+    let data: Vec<u8> = (0..64 * 1024)
+      .map(|_| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state as u8
+      })
+      .collect();
+
+    let mut u = Unstructured::new(&data);
+    for _ in 0..32 {
+      let Ok(program) = FuzzProgram::arbitrary(&mut u) else {
+        break;
+      };
+      assert_eq!(check(&program).unwrap(), Comparison::Matched);
+    }
+  }
+  fn outcome(gas: i64) -> Outcome {
+    Outcome {
+      status: ExecutionStatus::Trap,
+      registers: [0; 13],
+      memory: Some(vec![0; 4]),
+      gas,
+      segfault_address: 0,
+    }
+  }
+
+  #[test]
+  fn test_compare_instantiation_failures() {
+    let error = InitializationError::InstantiationError;
+    assert_eq!(
+      compare(Err(error), Err(error)),
+      Ok(Comparison::Uninstantiable(error))
+    );
+    assert!(compare(Err(error), Err(InitializationError::MemoryError)).is_err());
+    assert!(compare(Ok(outcome(10)), Err(error)).is_err());
+    assert!(compare(Err(error), Ok(outcome(10))).is_err());
+  }
+
+  #[test]
+  fn test_compare_outcomes() {
+    assert_eq!(
+      compare(Ok(outcome(10)), Ok(outcome(10))),
+      Ok(Comparison::Matched)
+    );
+    assert!(compare(Ok(outcome(10)), Ok(outcome(9))).is_err());
+  }
+}
@@ -0,0 +1,122 @@
+//! Disassembly of programs as seen by the reference decoder
+//!
+//! Meant to be shown next to the output of our `src/pvm/decoder` whenever
+//! both decoders disagree on a program.
+
+use std::{collections::BTreeSet, fmt::Write, slice};
+
+use polkavm::ProgramBlob;
+
+use crate::{blob::JamProgram, instructions::InstructionIndex};
+
+/// Format of a program passed across the FFI
+#[repr(C)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ProgramFormat {
+  /// A polkavm program container
+  PolkaVM = 0,
+  /// The JAM deblob format `E(|j|) ‖ E_1(z) ‖ E(|c|) ‖ E_z(j) ‖ c ‖ k`
+  Jam = 1,
+}
+
+/// Parses a program in the given format into a polkavm blob
+pub fn parse_program(
+  bytes: &[u8],
+  format: ProgramFormat,
+) -> Option<ProgramBlob> {
+  let raw = match format {
+    ProgramFormat::PolkaVM => bytes.to_vec(),
+    ProgramFormat::Jam => JamProgram::parse(bytes).ok()?.to_polkavm_blob(),
+  };
+  ProgramBlob::parse(raw.into()).ok()
+}
+
+/// Renders exports, the jump table and all instructions with their PCs,
+/// each basic block is introduced by a `@block` line
+pub fn disassemble(blob: &ProgramBlob) -> String {
+  let mut out = String::new();
+
+  // Writing into a String cannot fail
+  let _ = writeln!(out, "; exports");
+  for export in blob.exports() {
+    let _ = writeln!(
+      out,
+      "export {} @ {}",
+      String::from_utf8_lossy(export.symbol().as_bytes()),
+      export.program_counter().0
+    );
+  }
+
+  let _ = writeln!(out, "; jump table");
+  let mut jump_targets = BTreeSet::new();
+  for (index, target) in blob.jump_table().iter().enumerate() {
+    jump_targets.insert(target.0);
+    let _ = writeln!(out, "jump_table[{}] = {}", index, target.0);
+  }
+
+  let _ = writeln!(out, "; code");
+  let instructions = InstructionIndex::new(blob);
+  let block_starts: BTreeSet<u32> =
+    instructions.basic_block_starts().into_iter().collect();
+  for (pc, instruction) in instructions.iter() {
+    if block_starts.contains(&pc) {
+      let marker = if jump_targets.contains(&pc) {
+        " (jump target)"
+      } else {
+        ""
+      };
+      let _ = writeln!(out, "@block {}{}", pc, marker);
+    }
+    let _ = writeln!(out, "{:>6}: {}", pc, instruction.kind);
+  }
+
+  out
+}
+
+/// Disassembles a program into `out` as UTF-8 text
+///
+/// Returns the full length of the disassembly, at most `capacity` bytes are
+/// written. Call with a capacity of 0 to query the required size. Returns -1
+/// if the program cannot be parsed.
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts raw pointers as input
+#[no_mangle]
+pub unsafe extern "C" fn disassemble_program(
+  program: *const u8,
+  program_len: usize,
+  format: ProgramFormat,
+  out: *mut u8,
+  capacity: usize,
+) -> isize {
+  let bytes = slice::from_raw_parts(program, program_len);
+  let Some(blob) = parse_program(bytes, format) else {
+    return -1;
+  };
+
+  let text = disassemble(&blob);
+  if !out.is_null() {
+    let count = text.len().min(capacity);
+    std::ptr::copy_nonoverlapping(text.as_ptr(), out, count);
+  }
+  text.len() as isize
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_disassemble_jam_program() {
+    // The `inst_add_32` program from the PVM test vectors
+    let program = [0, 0, 3, 190, 135, 9, 1];
+    let blob = parse_program(&program, ProgramFormat::Jam).unwrap();
+    let text = disassemble(&blob);
+
+    assert!(text.contains("@block 0\n"));
+    assert!(text.contains("     0: "));
+    assert!(text.starts_with("; exports\n; jump table\n; code\n"));
+    assert!(parse_program(&[1, 2, 3], ProgramFormat::Jam).is_none());
+  }
+}
@@ -0,0 +1,167 @@
+//! Selecting where execution starts
+//!
+//! Executors start at PC 0, which is the refine entry of a JAM service.
+//! The other invocations enter at fixed offsets (accumulate at 5), polkavm
+//! blobs name their entry points through exports instead.
+
+use std::slice;
+
+use crate::ProgramExecutor;
+
+#[repr(C)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum EntryPointError {
+  /// The PC does not start a basic block
+  InvalidProgramCounter = 1,
+  UnknownExport = 2,
+  /// The executor has already stepped
+  AlreadyStarted = 3,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum EntryPoint<'a> {
+  ProgramCounter(u32),
+  Export(&'a [u8]),
+}
+
+impl ProgramExecutor {
+  /// Returns the PC of the export named `name`
+  pub fn export_program_counter(&self, name: &[u8]) -> Option<u32> {
+    self
+      .instance
+      .module()
+      .exports()
+      .find(|export| export.symbol().as_bytes() == name)
+      .map(|export| export.program_counter().0)
+  }
+
+  /// Sets where execution starts, must be called before the first step
+  pub fn set_entry_point(
+    &mut self,
+    entry_point: EntryPoint,
+  ) -> Result<(), EntryPointError> {
+    if self.started {
+      return Err(EntryPointError::AlreadyStarted);
+    }
+
+    let pc = match entry_point {
+      EntryPoint::ProgramCounter(pc) => pc,
+      EntryPoint::Export(name) => self
+        .export_program_counter(name)
+        .ok_or(EntryPointError::UnknownExport)?,
+    };
+
+    if self
+      .instructions
+      .basic_block_starts()
+      .binary_search(&pc)
+      .is_err()
+    {
+      return Err(EntryPointError::InvalidProgramCounter);
+    }
+
+    self.set_next_program_counter(pc);
+    Ok(())
+  }
+}
+
+/// Starts execution at `pc`
+///
+/// Returns 0 on success or an `EntryPointError` code
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts a raw pointer as input
+#[no_mangle]
+pub unsafe extern "C" fn executor_set_entry_pc(
+  executor: *mut ProgramExecutor,
+  pc: u32,
+) -> i32 {
+  match (&mut *executor).set_entry_point(EntryPoint::ProgramCounter(pc)) {
+    Ok(()) => 0,
+    Err(e) => e as i32,
+  }
+}
+
+/// Starts execution at the export named by the `name_len` bytes at `name`
+///
+/// Returns 0 on success or an `EntryPointError` code
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts raw pointers as input
+#[no_mangle]
+pub unsafe extern "C" fn executor_set_entry_export(
+  executor: *mut ProgramExecutor,
+  name: *const u8,
+  name_len: usize,
+) -> i32 {
+  let name = slice::from_raw_parts(name, name_len);
+  match (&mut *executor).set_entry_point(EntryPoint::Export(name)) {
+    Ok(()) => 0,
+    Err(e) => e as i32,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use polkavm::Reg;
+  use polkavm_common::{program::asm, writer::ProgramBlobBuilder};
+
+  use super::*;
+  use crate::{ExecutionStatus, MemoryPage};
+
+  #[test]
+  fn test_entry_point_by_export() {
+    let mut builder = ProgramBlobBuilder::new();
+    builder.add_export_by_basic_block(0, b"first");
+    builder.add_export_by_basic_block(1, b"second");
+    builder.set_code(
+      &[
+        asm::load_imm(Reg::T0, 1),
+        asm::ret(),
+        asm::load_imm(Reg::T0, 2),
+        asm::ret(),
+      ],
+      &[],
+    );
+    let program = builder.into_vec();
+    let registers = [0u64; 13];
+    let pages: [MemoryPage; 0] = [];
+
+    unsafe {
+      let mut executor = ProgramExecutor::new(
+        program.as_ptr(),
+        program.len(),
+        pages.as_ptr(),
+        0,
+        registers.as_ptr(),
+        10000,
+      )
+      .expect("Failed to create executor");
+
+      assert_eq!(
+        executor.set_entry_point(EntryPoint::Export(b"third")),
+        Err(EntryPointError::UnknownExport)
+      );
+      assert_eq!(
+        executor.set_entry_point(EntryPoint::ProgramCounter(1)),
+        Err(EntryPointError::InvalidProgramCounter)
+      );
+      assert_eq!(
+        executor.set_entry_point(EntryPoint::Export(b"second")),
+        Ok(())
+      );
+
+      executor.run_to_completion();
+      let result = executor.create_execution_result();
+      assert_eq!(result.status, ExecutionStatus::Trap);
+      assert_eq!(result.registers[Reg::T0 as usize], 2);
+
+      assert_eq!(
+        executor.set_entry_point(EntryPoint::Export(b"first")),
+        Err(EntryPointError::AlreadyStarted)
+      );
+    }
+  }
+}
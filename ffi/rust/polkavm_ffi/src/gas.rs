@@ -0,0 +1,116 @@
+//! Gas costs as charged by the reference
+//!
+//! polkavm charges the cost of a whole basic block when entering it. Listing
+//! the cost per block lets us tell divergences in the cost model apart from
+//! divergences in metering semantics, the gas consumed by every step is part
+//! of the step trace.
+
+use polkavm::ProgramCounter;
+
+use crate::ProgramExecutor;
+
+#[repr(C)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct BlockGasCost {
+  pub start_pc: u32,
+  pub cost: i64,
+}
+
+impl ProgramExecutor {
+  /// Returns the gas charged on entry of every basic block
+  pub fn block_gas_costs(&self) -> Vec<BlockGasCost> {
+    let module = self.instance.module();
+    self
+      .instructions
+      .basic_block_starts()
+      .into_iter()
+      .filter_map(|start_pc| {
+        let cost = module.calculate_gas_cost_for(ProgramCounter(start_pc))?;
+        Some(BlockGasCost { start_pc, cost })
+      })
+      .collect()
+  }
+}
+
+/// Writes the gas cost of each basic block into `costs_out`
+///
+/// Returns the total number of basic blocks, at most `capacity` entries are
+/// written. Call with a capacity of 0 to query the required size.
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts raw pointers as input
+#[no_mangle]
+pub unsafe extern "C" fn executor_block_gas_costs(
+  executor: *const ProgramExecutor,
+  costs_out: *mut BlockGasCost,
+  capacity: usize,
+) -> usize {
+  let costs = (&*executor).block_gas_costs();
+  if !costs_out.is_null() {
+    let count = costs.len().min(capacity);
+    std::ptr::copy_nonoverlapping(costs.as_ptr(), costs_out, count);
+  }
+  costs.len()
+}
+
+#[cfg(test)]
+mod tests {
+  use polkavm::Reg;
+  use polkavm_common::{program::asm, writer::ProgramBlobBuilder};
+
+  use super::*;
+  use crate::MemoryPage;
+
+  #[test]
+  fn test_block_gas_costs_match_charged_gas() {
+    let mut builder = ProgramBlobBuilder::new();
+    builder.add_export_by_basic_block(0, b"main");
+    builder.set_code(
+      &[
+        asm::load_imm(Reg::T0, 1),
+        asm::load_imm(Reg::T1, 2),
+        asm::fallthrough(),
+        asm::add_32(Reg::T2, Reg::T0, Reg::T1),
+        asm::ret(),
+      ],
+      &[],
+    );
+    let program = builder.into_vec();
+    let registers = [0u64; 13];
+    let pages: [MemoryPage; 0] = [];
+
+    unsafe {
+      let mut executor = ProgramExecutor::new(
+        program.as_ptr(),
+        program.len(),
+        pages.as_ptr(),
+        0,
+        registers.as_ptr(),
+        10000,
+      )
+      .expect("Failed to create executor");
+
+      let costs = executor.block_gas_costs();
+      assert_eq!(costs.len(), 2);
+      assert_eq!(costs[0].start_pc, 0);
+
+      let mut charged = Vec::new();
+      while !executor.is_finished() {
+        executor.advance();
+        if executor.last_trace.gas_charged != 0 {
+          charged.push((
+            executor.last_trace.pc_before,
+            executor.last_trace.gas_charged,
+          ));
+        }
+      }
+
+      let expected: Vec<_> = costs
+        .iter()
+        .map(|block| (block.start_pc, block.cost))
+        .collect();
+      assert_eq!(charged, expected);
+    }
+  }
+}
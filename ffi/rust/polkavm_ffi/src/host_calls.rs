@@ -0,0 +1,992 @@
+//! Reference versions of the JAM general host calls
+//!
+//! Implements gas, fetch, lookup, read, write, info and log (Appendix B.7 of
+//! the graypaper and JIP-1) over an in-memory model of service accounts,
+//! plus the inner machine calls of refine from `inner_machine`. The
+//! caller seeds the model, runs an invocation and then compares the
+//! registers, memory and storage side effects against
+//! `src/pvm_invocations/host_calls_general.zig`.
+
+use std::{collections::BTreeMap, slice};
+
+use polkavm::Reg;
+
+use crate::{
+  limits::Interrupted, ExecutionResult, ExecutionStatus, ProgramExecutor,
+};
+
+/// Gas charged by every general host call
+const HOST_CALL_GAS: i64 = 10;
+
+/// B_S, B_I and B_L, the same for the tiny and full parameter sets
+const BASIC_SERVICE_BALANCE: u64 = 100;
+const MIN_BALANCE_PER_ITEM: u64 = 10;
+const MIN_BALANCE_PER_OCTET: u64 = 1;
+
+pub mod host_call_id {
+  pub const GAS: u32 = 0;
+  pub const FETCH: u32 = 1;
+  pub const LOOKUP: u32 = 2;
+  pub const READ: u32 = 3;
+  pub const WRITE: u32 = 4;
+  pub const INFO: u32 = 5;
+  pub const MACHINE: u32 = 8;
+  pub const PEEK: u32 = 9;
+  pub const POKE: u32 = 10;
+  pub const PAGES: u32 = 11;
+  pub const INVOKE: u32 = 12;
+  pub const EXPUNGE: u32 = 13;
+  pub const LOG: u32 = 100;
+}
+
+pub mod return_code {
+  pub const OK: u64 = 0;
+  pub const NONE: u64 = u64::MAX;
+  pub const WHAT: u64 = u64::MAX - 1;
+  pub const OOB: u64 = u64::MAX - 2;
+  pub const WHO: u64 = u64::MAX - 3;
+  pub const FULL: u64 = u64::MAX - 4;
+  pub const HUH: u64 = u64::MAX - 8;
+}
+
+/// Scalar fields of a service account as seeded over FFI
+#[repr(C)]
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct ServiceInfo {
+  pub code_hash: [u8; 32],
+  pub balance: u64,
+  pub min_item_gas: u64,
+  pub min_memo_gas: u64,
+  /// a_f, the free storage allowance
+  pub storage_offset: u64,
+  pub creation_slot: u32,
+  pub last_accumulation_slot: u32,
+  pub parent_service: u32,
+  /// a_i, including items that are not part of this model such as
+  /// preimage lookups
+  pub footprint_items: u32,
+  /// a_o, including bytes that are not part of this model
+  pub footprint_bytes: u64,
+}
+
+impl ServiceInfo {
+  /// a_t = max(0, B_S + B_I·a_i + B_L·a_o - a_f)
+  fn threshold(items: u32, bytes: u64, storage_offset: u64) -> u64 {
+    (BASIC_SERVICE_BALANCE
+      + MIN_BALANCE_PER_ITEM * items as u64
+      + MIN_BALANCE_PER_OCTET * bytes)
+      .saturating_sub(storage_offset)
+  }
+
+  /// E(a_c, E_8(a_b, a_t, a_g, a_m, a_o), E_4(a_i), E_8(a_f),
+  /// E_4(a_r, a_a, a_p))
+  fn encode(&self) -> Vec<u8> {
+    let threshold = Self::threshold(
+      self.footprint_items,
+      self.footprint_bytes,
+      self.storage_offset,
+    );
+
+    let mut out = Vec::with_capacity(96);
+    out.extend_from_slice(&self.code_hash);
+    for value in [
+      self.balance,
+      threshold,
+      self.min_item_gas,
+      self.min_memo_gas,
+      self.footprint_bytes,
+    ] {
+      out.extend_from_slice(&value.to_le_bytes());
+    }
+    out.extend_from_slice(&self.footprint_items.to_le_bytes());
+    out.extend_from_slice(&self.storage_offset.to_le_bytes());
+    for value in [
+      self.creation_slot,
+      self.last_accumulation_slot,
+      self.parent_service,
+    ] {
+      out.extend_from_slice(&value.to_le_bytes());
+    }
+    out
+  }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ServiceAccount {
+  pub info: ServiceInfo,
+  pub storage: BTreeMap<Vec<u8>, Vec<u8>>,
+  pub preimages: BTreeMap<[u8; 32], Vec<u8>>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct LogEntry {
+  pub level: u64,
+  pub target: Vec<u8>,
+  pub message: Vec<u8>,
+}
+
+/// Host state the general host calls run against
+#[derive(Debug, Default, Clone)]
+pub struct HostEnvironment {
+  /// The service being invoked
+  pub service_id: u32,
+  pub accounts: BTreeMap<u32, ServiceAccount>,
+  /// Values returned by fetch, keyed by selector and the indices it uses
+  pub fetch_data: BTreeMap<(u64, u64, u64), Vec<u8>>,
+  pub log: Vec<LogEntry>,
+}
+
+/// How execution continues after a host call
+#[repr(C)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum HostCallOutcome {
+  Continue = 0,
+  Panic = 1,
+  OutOfGas = 2,
+  /// Cut short by the run limits or an interrupt while an inner machine
+  /// ran, the host call is made again when the run continues
+  Interrupted = 3,
+}
+
+impl HostEnvironment {
+  pub fn new(service_id: u32) -> Self {
+    Self {
+      service_id,
+      ..Self::default()
+    }
+  }
+
+  /// Resolves s*, where 2^64 - 1 stands for the invoked service
+  fn resolve_service(&self, register: u64) -> Option<&ServiceAccount> {
+    let service_id = if register == return_code::NONE {
+      self.service_id
+    } else {
+      u32::try_from(register).ok()?
+    };
+    self.accounts.get(&service_id)
+  }
+
+  /// Fetch selectors use ω11 and ω12 as indices only where defined
+  fn fetch_key(selector: u64, w11: u64, w12: u64) -> (u64, u64, u64) {
+    match selector {
+      3 | 5 => (selector, w11, w12),
+      4 | 6 | 12 | 13 | 15 => (selector, w11, 0),
+      _ => (selector, 0, 0),
+    }
+  }
+
+  /// Runs the host call `index` on an executor stopped on it
+  pub fn dispatch(
+    &mut self,
+    executor: &mut ProgramExecutor,
+    index: u32,
+  ) -> HostCallOutcome {
+    use host_call_id::*;
+
+    let gas = executor.gas() - HOST_CALL_GAS;
+    executor.set_gas(gas);
+
+    // Unknown host calls only charge gas and set WHAT
+    if !matches!(index, GAS..=INFO | MACHINE..=EXPUNGE | LOG) {
+      executor.instance.set_reg(Reg::A0, return_code::WHAT);
+      return HostCallOutcome::Continue;
+    }
+    if gas < 0 {
+      return HostCallOutcome::OutOfGas;
+    }
+
+    let result = match index {
+      GAS => Some(gas as u64),
+      FETCH => self.fetch(executor),
+      LOOKUP => self.lookup(executor),
+      READ => self.read(executor),
+      WRITE => self.write(executor),
+      INFO => self.info(executor),
+      MACHINE => executor.machine_host_call(),
+      PEEK => executor.peek_host_call(),
+      POKE => executor.poke_host_call(),
+      PAGES => executor.pages_host_call(),
+      INVOKE => match executor.invoke_host_call() {
+        Ok(result) => result,
+        Err(Interrupted) => {
+          executor.set_gas(gas + HOST_CALL_GAS);
+          return HostCallOutcome::Interrupted;
+        }
+      },
+      EXPUNGE => executor.expunge_host_call(),
+      _ => Some(self.log(executor)),
+    };
+
+    match result {
+      Some(value) => {
+        executor.instance.set_reg(Reg::A0, value);
+        HostCallOutcome::Continue
+      }
+      None => HostCallOutcome::Panic,
+    }
+  }
+
+  /// Ω_Y, returns None when the executor has to panic
+  fn fetch(&self, executor: &mut ProgramExecutor) -> Option<u64> {
+    let [output, offset, limit, selector, w11, w12] = registers(executor);
+    let Some(value) = self.fetch_data.get(&Self::fetch_key(selector, w11, w12))
+    else {
+      return Some(return_code::NONE);
+    };
+    write_range(executor, output, value, offset, limit)
+  }
+
+  /// Ω_L
+  fn lookup(&self, executor: &mut ProgramExecutor) -> Option<u64> {
+    let [service, hash_address, output, offset, limit, _] = registers(executor);
+    let hash: [u8; 32] =
+      read_guest(executor, hash_address, 32)?.try_into().ok()?;
+
+    let Some(preimage) = self
+      .resolve_service(service)
+      .and_then(|account| account.preimages.get(&hash))
+    else {
+      return Some(return_code::NONE);
+    };
+    write_range(executor, output, preimage, offset, limit)
+  }
+
+  /// Ω_R
+  fn read(&self, executor: &mut ProgramExecutor) -> Option<u64> {
+    let [service, key_address, key_len, output, offset, limit] =
+      registers(executor);
+    let key = read_guest(executor, key_address, key_len)?;
+
+    let Some(value) = self
+      .resolve_service(service)
+      .and_then(|account| account.storage.get(&key))
+    else {
+      return Some(return_code::NONE);
+    };
+    write_range(executor, output, value, offset, limit)
+  }
+
+  /// Ω_W, checks the balance before touching storage
+  fn write(&mut self, executor: &mut ProgramExecutor) -> Option<u64> {
+    let [key_address, key_len, value_address, value_len, _, _] =
+      registers(executor);
+    let account = self.accounts.get_mut(&self.service_id)?;
+    let key = read_guest(executor, key_address, key_len)?;
+
+    let prior_len = |value: Option<Vec<u8>>| {
+      value.map_or(return_code::NONE, |value| value.len() as u64)
+    };
+
+    if value_len == 0 {
+      let prior = account.storage.remove(&key);
+      if let Some(value) = &prior {
+        let info = &mut account.info;
+        info.footprint_items = info.footprint_items.saturating_sub(1);
+        info.footprint_bytes = info
+          .footprint_bytes
+          .saturating_sub((34 + key.len() + value.len()) as u64);
+      }
+      return Some(prior_len(prior));
+    }
+
+    let value = read_guest(executor, value_address, value_len)?;
+    let (items, bytes) = match account.storage.get(&key) {
+      Some(old) => (
+        account.info.footprint_items,
+        account
+          .info
+          .footprint_bytes
+          .saturating_sub(old.len() as u64)
+          + value.len() as u64,
+      ),
+      None => (
+        account.info.footprint_items + 1,
+        account.info.footprint_bytes + (34 + key.len() + value.len()) as u64,
+      ),
+    };
+    let threshold =
+      ServiceInfo::threshold(items, bytes, account.info.storage_offset);
+    if threshold > account.info.balance {
+      return Some(return_code::FULL);
+    }
+
+    account.info.footprint_items = items;
+    account.info.footprint_bytes = bytes;
+    Some(prior_len(account.storage.insert(key, value)))
+  }
+
+  /// Ω_I
+  fn info(&self, executor: &mut ProgramExecutor) -> Option<u64> {
+    let [service, output, offset, limit, _, _] = registers(executor);
+    let Some(account) = self.resolve_service(service) else {
+      return Some(return_code::NONE);
+    };
+    write_range(executor, output, &account.info.encode(), offset, limit)
+  }
+
+  /// JIP-1 logging, always returns WHAT
+  fn log(&mut self, executor: &mut ProgramExecutor) -> u64 {
+    let [level, target_address, target_len, message_address, message_len, _] =
+      registers(executor);
+    let target = if target_address == 0 && target_len == 0 {
+      Vec::new()
+    } else {
+      read_guest(executor, target_address, target_len).unwrap_or_default()
+    };
+
+    if let Some(message) = read_guest(executor, message_address, message_len) {
+      self.log.push(LogEntry {
+        level,
+        target,
+        message,
+      });
+    }
+    return_code::WHAT
+  }
+}
+
+impl ProgramExecutor {
+  /// Runs the host call the executor is stopped on
+  ///
+  /// Returns false if the executor is not stopped on a host call.
+  pub fn handle_host_call(&mut self, env: &mut HostEnvironment) -> bool {
+    let Some(index) = self.host_call.take() else {
+      return false;
+    };
+
+    self.current_status = match env.dispatch(self, index) {
+      HostCallOutcome::Continue => ExecutionStatus::Running,
+      HostCallOutcome::Panic => ExecutionStatus::Trap,
+      HostCallOutcome::OutOfGas => ExecutionStatus::OutOfGas,
+      HostCallOutcome::Interrupted => {
+        self.host_call = Some(index);
+        ExecutionStatus::Interrupted
+      }
+    };
+    true
+  }
+
+  /// Runs until the program finishes or a run limit is reached, serving
+  /// host calls from `env`
+  ///
+  /// A host call the executor is stopped on is served first, so a run
+  /// interrupted inside a host call continues with it.
+  pub fn run_with_host(&mut self, env: &mut HostEnvironment) {
+    self.run_serving_host_calls(|executor| executor.handle_host_call(env));
+  }
+}
+
+/// ω7 to ω12
+pub(crate) fn registers(executor: &ProgramExecutor) -> [u64; 6] {
+  std::array::from_fn(|i| executor.instance.reg(Reg::ALL[7 + i]))
+}
+
+/// Reads guest memory, None if any part of the range is inaccessible
+pub(crate) fn read_guest(
+  executor: &ProgramExecutor,
+  address: u64,
+  len: u64,
+) -> Option<Vec<u8>> {
+  let address = u32::try_from(address).ok()?;
+  let len = u32::try_from(len).ok()?;
+  // Checked before allocating, the length comes straight from a register
+  if !executor.page_map.is_accessible(address, len, false) {
+    return None;
+  }
+
+  let mut buffer = vec![0; len as usize];
+  executor.read_memory_into(address, &mut buffer).ok()?;
+  Some(buffer)
+}
+
+/// Writes `value[f..f + l]` with f = min(offset, |v|) and
+/// l = min(limit, |v| - f) to `output` and returns |v|, None if the output
+/// is not writable
+fn write_range(
+  executor: &mut ProgramExecutor,
+  output: u64,
+  value: &[u8],
+  offset: u64,
+  limit: u64,
+) -> Option<u64> {
+  let start = offset.min(value.len() as u64) as usize;
+  let len = limit.min((value.len() - start) as u64) as usize;
+  if len > 0 {
+    let output = u32::try_from(output).ok()?;
+    executor
+      .write_memory(output, &value[start..start + len])
+      .ok()?;
+  }
+  Some(value.len() as u64)
+}
+
+/// Creates an empty host environment invoking `service_id`
+#[no_mangle]
+pub extern "C" fn create_host_environment(
+  service_id: u32,
+) -> *mut HostEnvironment {
+  Box::into_raw(Box::new(HostEnvironment::new(service_id)))
+}
+
+/// Frees a host environment
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts a raw pointer as input
+#[no_mangle]
+pub unsafe extern "C" fn free_host_environment(env: *mut HostEnvironment) {
+  if !env.is_null() {
+    drop(Box::from_raw(env));
+  }
+}
+
+/// Creates or replaces the scalar fields of a service account
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts raw pointers as input
+#[no_mangle]
+pub unsafe extern "C" fn host_env_set_service(
+  env: *mut HostEnvironment,
+  service_id: u32,
+  info: *const ServiceInfo,
+) {
+  (&mut *env).accounts.entry(service_id).or_default().info = *info;
+}
+
+/// Reads the scalar fields of a service account, returns false if the
+/// service does not exist
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts raw pointers as input
+#[no_mangle]
+pub unsafe extern "C" fn host_env_get_service(
+  env: *const HostEnvironment,
+  service_id: u32,
+  info_out: *mut ServiceInfo,
+) -> bool {
+  match (&*env).accounts.get(&service_id) {
+    Some(account) => {
+      *info_out = account.info;
+      true
+    }
+    None => false,
+  }
+}
+
+/// Seeds a storage item without touching the footprint, returns false if
+/// the service does not exist
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts raw pointers as input
+#[no_mangle]
+pub unsafe extern "C" fn host_env_set_storage(
+  env: *mut HostEnvironment,
+  service_id: u32,
+  key: *const u8,
+  key_len: usize,
+  value: *const u8,
+  value_len: usize,
+) -> bool {
+  let Some(account) = (&mut *env).accounts.get_mut(&service_id) else {
+    return false;
+  };
+  account.storage.insert(
+    slice::from_raw_parts(key, key_len).to_vec(),
+    slice::from_raw_parts(value, value_len).to_vec(),
+  );
+  true
+}
+
+/// Copies a storage value into `out`
+///
+/// Returns the full length of the value or -1 if it does not exist, at
+/// most `capacity` bytes are written.
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts raw pointers as input
+#[no_mangle]
+pub unsafe extern "C" fn host_env_get_storage(
+  env: *const HostEnvironment,
+  service_id: u32,
+  key: *const u8,
+  key_len: usize,
+  out: *mut u8,
+  capacity: usize,
+) -> isize {
+  let key = slice::from_raw_parts(key, key_len);
+  let Some(value) = (&*env)
+    .accounts
+    .get(&service_id)
+    .and_then(|account| account.storage.get(key))
+  else {
+    return -1;
+  };
+  copy_out(value, out, capacity)
+}
+
+/// Returns the number of storage items of a service
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts a raw pointer as input
+#[no_mangle]
+pub unsafe extern "C" fn host_env_storage_count(
+  env: *const HostEnvironment,
+  service_id: u32,
+) -> usize {
+  (&*env)
+    .accounts
+    .get(&service_id)
+    .map_or(0, |account| account.storage.len())
+}
+
+/// Seeds the preimage of the 32 byte `hash`, returns false if the service
+/// does not exist
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts raw pointers as input
+#[no_mangle]
+pub unsafe extern "C" fn host_env_set_preimage(
+  env: *mut HostEnvironment,
+  service_id: u32,
+  hash: *const [u8; 32],
+  data: *const u8,
+  len: usize,
+) -> bool {
+  let Some(account) = (&mut *env).accounts.get_mut(&service_id) else {
+    return false;
+  };
+  account
+    .preimages
+    .insert(*hash, slice::from_raw_parts(data, len).to_vec());
+  true
+}
+
+/// Seeds the value fetch returns for a selector, indices a selector does
+/// not use are ignored
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts raw pointers as input
+#[no_mangle]
+pub unsafe extern "C" fn host_env_set_fetch(
+  env: *mut HostEnvironment,
+  selector: u64,
+  index_1: u64,
+  index_2: u64,
+  data: *const u8,
+  len: usize,
+) {
+  (&mut *env).fetch_data.insert(
+    HostEnvironment::fetch_key(selector, index_1, index_2),
+    slice::from_raw_parts(data, len).to_vec(),
+  );
+}
+
+/// Returns the number of messages logged so far
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts a raw pointer as input
+#[no_mangle]
+pub unsafe extern "C" fn host_env_log_count(
+  env: *const HostEnvironment,
+) -> usize {
+  (&*env).log.len()
+}
+
+/// Copies a logged message into `out` and its level into `level_out`
+///
+/// Returns the full length of the message or -1 for an invalid index, at
+/// most `capacity` bytes are written.
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts raw pointers as input
+#[no_mangle]
+pub unsafe extern "C" fn host_env_log_message(
+  env: *const HostEnvironment,
+  index: usize,
+  level_out: *mut u64,
+  out: *mut u8,
+  capacity: usize,
+) -> isize {
+  let Some(entry) = (&*env).log.get(index) else {
+    return -1;
+  };
+  *level_out = entry.level;
+  copy_out(&entry.message, out, capacity)
+}
+
+/// Runs the host call the executor is stopped on against `env`, returns
+/// false if it is not stopped on a host call
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts raw pointers as input
+#[no_mangle]
+pub unsafe extern "C" fn executor_handle_host_call(
+  executor: *mut ProgramExecutor,
+  env: *mut HostEnvironment,
+) -> bool {
+  (&mut *executor).handle_host_call(&mut *env)
+}
+
+/// Runs until the program finishes or a run limit is reached, serving
+/// host calls from `env`
+///
+/// A run interrupted while an inner machine ran stays on the `invoke` host
+/// call, calling this again continues it.
+///
+/// # Safety
+///
+/// This function is unsafe because it:
+/// - Accepts raw pointers as input
+/// - Returns pages borrowed from the executor, they must not be used after
+///   the next step or after the executor is freed
+#[no_mangle]
+pub unsafe extern "C" fn executor_run_with_host(
+  executor: *mut ProgramExecutor,
+  env: *mut HostEnvironment,
+) -> ExecutionResult {
+  let executor = &mut *executor;
+  executor.run_with_host(&mut *env);
+  executor.create_execution_result()
+}
+
+unsafe fn copy_out(value: &[u8], out: *mut u8, capacity: usize) -> isize {
+  if !out.is_null() {
+    let count = value.len().min(capacity);
+    std::ptr::copy_nonoverlapping(value.as_ptr(), out, count);
+  }
+  value.len() as isize
+}
+
+#[cfg(test)]
+mod tests {
+  use polkavm_common::{
+    program::{asm, Instruction},
+    writer::ProgramBlobBuilder,
+  };
+
+  use super::*;
+  use crate::MemoryPage;
+
+  const DATA: u32 = 0x20000;
+
+  fn executor_for(code: &[Instruction]) -> ProgramExecutor {
+    let mut builder = ProgramBlobBuilder::new_64bit();
+    builder.add_export_by_basic_block(0, b"main");
+    builder.set_code(code, &[]);
+    let program = builder.into_vec();
+
+    let mut memory = vec![0u8; 4096];
+    memory[..3].copy_from_slice(b"key");
+    memory[3..8].copy_from_slice(b"value");
+    let page = MemoryPage {
+      address: DATA,
+      data: memory.as_mut_ptr(),
+      size: 4096,
+      is_writable: true,
+    };
+    let registers = [0u64; 13];
+
+    unsafe {
+      ProgramExecutor::new(
+        program.as_ptr(),
+        program.len(),
+        &page,
+        1,
+        registers.as_ptr(),
+        10000,
+      )
+      .expect("Failed to create executor")
+    }
+  }
+
+  fn environment() -> HostEnvironment {
+    let mut env = HostEnvironment::new(7);
+    env.accounts.insert(
+      7,
+      ServiceAccount {
+        info: ServiceInfo {
+          balance: 1000,
+          ..ServiceInfo::default()
+        },
+        ..ServiceAccount::default()
+      },
+    );
+    env
+  }
+
+  #[test]
+  fn test_write_then_read_storage() {
+    let mut executor = executor_for(&[
+      // write(key, "value")
+      asm::load_imm(Reg::A0, DATA),
+      asm::load_imm(Reg::A1, 3),
+      asm::load_imm(Reg::A2, DATA + 3),
+      asm::load_imm(Reg::A3, 5),
+      asm::ecalli(host_call_id::WRITE),
+      asm::move_reg(Reg::S0, Reg::A0),
+      // read(self, key) into DATA + 16
+      asm::load_imm64(Reg::A0, u64::MAX),
+      asm::load_imm(Reg::A1, DATA),
+      asm::load_imm(Reg::A2, 3),
+      asm::load_imm(Reg::A3, DATA + 16),
+      asm::load_imm(Reg::A4, 0),
+      asm::load_imm(Reg::A5, 5),
+      asm::ecalli(host_call_id::READ),
+      asm::ret(),
+    ]);
+    let mut env = environment();
+
+    executor.run_with_host(&mut env);
+    assert_eq!(executor.current_status, ExecutionStatus::Trap);
+    assert_eq!(executor.register(Reg::S0 as u32), Ok(return_code::NONE));
+    assert_eq!(executor.register(Reg::A0 as u32), Ok(5));
+
+    let mut read_back = [0u8; 5];
+    executor
+      .read_memory_into(DATA + 16, &mut read_back)
+      .unwrap();
+    assert_eq!(&read_back, b"value");
+
+    let account = &env.accounts[&7];
+    assert_eq!(account.storage[b"key".as_slice()], b"value");
+    assert_eq!(account.info.footprint_items, 1);
+    assert_eq!(account.info.footprint_bytes, 34 + 3 + 5);
+  }
+
+  #[test]
+  fn test_write_beyond_balance_is_full() {
+    let mut executor = executor_for(&[
+      asm::load_imm(Reg::A0, DATA),
+      asm::load_imm(Reg::A1, 3),
+      asm::load_imm(Reg::A2, DATA + 3),
+      asm::load_imm(Reg::A3, 5),
+      asm::ecalli(host_call_id::WRITE),
+      asm::ret(),
+    ]);
+    let mut env = environment();
+    env.accounts.get_mut(&7).unwrap().info.balance = 0;
+
+    executor.run_with_host(&mut env);
+    assert_eq!(executor.register(Reg::A0 as u32), Ok(return_code::FULL));
+    assert!(env.accounts[&7].storage.is_empty());
+  }
+
+  #[test]
+  fn test_inaccessible_memory_panics() {
+    let mut executor = executor_for(&[
+      asm::load_imm64(Reg::A0, u64::MAX),
+      asm::load_imm(Reg::A1, 0x10),
+      asm::load_imm(Reg::A2, 3),
+      asm::ecalli(host_call_id::READ),
+      asm::ret(),
+    ]);
+    let mut env = environment();
+
+    executor.run_with_host(&mut env);
+    assert_eq!(executor.current_status, ExecutionStatus::Trap);
+    assert_eq!(executor.register(Reg::A0 as u32), Ok(u64::MAX));
+  }
+  fn read_back(
+    executor: &ProgramExecutor,
+    address: u32,
+    len: usize,
+  ) -> Vec<u8> {
+    let mut buffer = vec![0u8; len];
+    executor.read_memory_into(address, &mut buffer).unwrap();
+    buffer
+  }
+
+  #[test]
+  fn test_gas_returns_gas_after_charge() {
+    let mut executor =
+      executor_for(&[asm::ecalli(host_call_id::GAS), asm::ret()]);
+    let mut env = environment();
+
+    executor.run_until_stopped(|executor| {
+      executor.current_status == ExecutionStatus::HostCall
+    });
+    let gas = executor.gas();
+    assert!(executor.handle_host_call(&mut env));
+    assert_eq!(executor.gas(), gas - HOST_CALL_GAS);
+    assert_eq!(
+      executor.register(Reg::A0 as u32),
+      Ok((gas - HOST_CALL_GAS) as u64)
+    );
+  }
+
+  #[test]
+  fn test_fetch_writes_range_of_value() {
+    let mut executor = executor_for(&[
+      // fetch(4, 2) with a stray ω12, bytes 1..4 to DATA + 32
+      asm::load_imm(Reg::A0, DATA + 32),
+      asm::load_imm(Reg::A1, 1),
+      asm::load_imm(Reg::A2, 3),
+      asm::load_imm(Reg::A3, 4),
+      asm::load_imm(Reg::A4, 2),
+      asm::load_imm(Reg::A5, 99),
+      asm::ecalli(host_call_id::FETCH),
+      asm::move_reg(Reg::S0, Reg::A0),
+      // fetch(5, 2, 99) was never seeded
+      asm::load_imm(Reg::A0, DATA + 32),
+      asm::load_imm(Reg::A3, 5),
+      asm::ecalli(host_call_id::FETCH),
+      asm::ret(),
+    ]);
+    let mut env = environment();
+    env
+      .fetch_data
+      .insert(HostEnvironment::fetch_key(4, 2, 0), b"abcdef".to_vec());
+
+    executor.run_with_host(&mut env);
+    assert_eq!(executor.current_status, ExecutionStatus::Trap);
+    assert_eq!(executor.register(Reg::S0 as u32), Ok(6));
+    assert_eq!(executor.register(Reg::A0 as u32), Ok(return_code::NONE));
+    assert_eq!(read_back(&executor, DATA + 32, 4), b"bcd\0");
+  }
+
+  #[test]
+  fn test_fetch_key_folds_unused_indices() {
+    assert_eq!(HostEnvironment::fetch_key(3, 1, 2), (3, 1, 2));
+    assert_eq!(HostEnvironment::fetch_key(5, 1, 2), (5, 1, 2));
+    for selector in [4, 6, 12, 13, 15] {
+      assert_eq!(HostEnvironment::fetch_key(selector, 1, 2), (selector, 1, 0));
+    }
+    for selector in [0, 1, 7, 14] {
+      assert_eq!(HostEnvironment::fetch_key(selector, 1, 2), (selector, 0, 0));
+    }
+  }
+
+  #[test]
+  fn test_lookup_preimage() {
+    let hash = [0x11u8; 32];
+    let mut executor = executor_for(&[
+      // lookup on a service that does not exist
+      asm::load_imm(Reg::A0, 8),
+      asm::load_imm(Reg::A1, DATA + 64),
+      asm::load_imm(Reg::A2, DATA + 128),
+      asm::load_imm(Reg::A3, 0),
+      asm::load_imm(Reg::A4, 100),
+      asm::ecalli(host_call_id::LOOKUP),
+      asm::move_reg(Reg::S0, Reg::A0),
+      // lookup on the invoked service
+      asm::load_imm64(Reg::A0, u64::MAX),
+      asm::ecalli(host_call_id::LOOKUP),
+      asm::ret(),
+    ]);
+    executor.write_memory(DATA + 64, &hash).unwrap();
+    let mut env = environment();
+    env
+      .accounts
+      .get_mut(&7)
+      .unwrap()
+      .preimages
+      .insert(hash, b"preimage".to_vec());
+
+    executor.run_with_host(&mut env);
+    assert_eq!(executor.current_status, ExecutionStatus::Trap);
+    assert_eq!(executor.register(Reg::S0 as u32), Ok(return_code::NONE));
+    assert_eq!(executor.register(Reg::A0 as u32), Ok(8));
+    assert_eq!(read_back(&executor, DATA + 128, 8), b"preimage");
+  }
+
+  #[test]
+  fn test_info_encodes_service() {
+    let mut executor = executor_for(&[
+      asm::load_imm64(Reg::A0, u64::MAX),
+      asm::load_imm(Reg::A1, DATA + 256),
+      asm::load_imm(Reg::A2, 0),
+      asm::load_imm(Reg::A3, 96),
+      asm::ecalli(host_call_id::INFO),
+      asm::ret(),
+    ]);
+    let info = ServiceInfo {
+      code_hash: [0xcc; 32],
+      balance: 1000,
+      min_item_gas: 3,
+      min_memo_gas: 4,
+      storage_offset: 30,
+      creation_slot: 5,
+      last_accumulation_slot: 6,
+      parent_service: 9,
+      footprint_items: 2,
+      footprint_bytes: 50,
+    };
+    let mut env = environment();
+    env.accounts.get_mut(&7).unwrap().info = info;
+
+    executor.run_with_host(&mut env);
+    assert_eq!(executor.register(Reg::A0 as u32), Ok(96));
+
+    // a_t = B_S + B_I·2 + B_L·50 - 30
+    let threshold: u64 = 100 + 10 * 2 + 50 - 30;
+    let mut expected = vec![0xcc; 32];
+    for value in [1000u64, threshold, 3, 4, 50] {
+      expected.extend_from_slice(&value.to_le_bytes());
+    }
+    expected.extend_from_slice(&2u32.to_le_bytes());
+    expected.extend_from_slice(&30u64.to_le_bytes());
+    for value in [5u32, 6, 9] {
+      expected.extend_from_slice(&value.to_le_bytes());
+    }
+    assert_eq!(read_back(&executor, DATA + 256, 96), expected);
+  }
+
+  #[test]
+  fn test_threshold_saturates() {
+    assert_eq!(ServiceInfo::threshold(0, 0, 0), 100);
+    assert_eq!(ServiceInfo::threshold(3, 7, 0), 100 + 30 + 7);
+    assert_eq!(ServiceInfo::threshold(3, 7, 1000), 0);
+  }
+
+  #[test]
+  fn test_log_records_message() {
+    let mut executor = executor_for(&[
+      // log(2, "key", "value")
+      asm::load_imm(Reg::A0, 2),
+      asm::load_imm(Reg::A1, DATA),
+      asm::load_imm(Reg::A2, 3),
+      asm::load_imm(Reg::A3, DATA + 3),
+      asm::load_imm(Reg::A4, 5),
+      asm::ecalli(host_call_id::LOG),
+      asm::move_reg(Reg::S0, Reg::A0),
+      // log(1, no target, "val")
+      asm::load_imm(Reg::A0, 1),
+      asm::load_imm(Reg::A1, 0),
+      asm::load_imm(Reg::A2, 0),
+      asm::load_imm(Reg::A4, 3),
+      asm::ecalli(host_call_id::LOG),
+      asm::ret(),
+    ]);
+    let mut env = environment();
+
+    executor.run_with_host(&mut env);
+    assert_eq!(executor.current_status, ExecutionStatus::Trap);
+    assert_eq!(executor.register(Reg::S0 as u32), Ok(return_code::WHAT));
+    assert_eq!(executor.register(Reg::A0 as u32), Ok(return_code::WHAT));
+    assert_eq!(
+      env.log,
+      [
+        LogEntry {
+          level: 2,
+          target: b"key".to_vec(),
+          message: b"value".to_vec(),
+        },
+        LogEntry {
+          level: 1,
+          target: Vec::new(),
+          message: b"val".to_vec(),
+        },
+      ]
+    );
+  }
+}
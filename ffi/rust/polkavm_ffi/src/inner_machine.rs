@@ -0,0 +1,442 @@
+//! Inner PVM machines for the refine host calls
+//!
+//! Every executor owns a registry of child machines created through
+//! `machine` (Ω_M). Children are executors themselves, so paging, gas
+//! metering and host call detection behave exactly as on the top level.
+//! Children run on what is left of the run limits of their parent.
+//! Executor snapshots do not include child machines, so they are refused
+//! while any exist and restoring one drops all children.
+
+use std::collections::BTreeMap;
+
+use polkavm::Reg;
+
+use crate::{
+  blob::JamProgram,
+  host_calls::{read_guest, registers, return_code},
+  limits::Interrupted,
+  memory_map::{PageInfo, PAGE_SIZE},
+  ExecutionStatus, ProgramExecutor,
+};
+
+/// Exit reasons `invoke` reports in ω7
+pub mod exit_reason {
+  pub const HALT: u64 = 0;
+  pub const PANIC: u64 = 1;
+  pub const FAULT: u64 = 2;
+  pub const HOST: u64 = 3;
+  pub const OOG: u64 = 4;
+}
+
+/// Size of the gas and register block `invoke` reads and writes back
+const INVOKE_STATE_SIZE: u64 = 8 + 13 * 8;
+
+#[derive(Default)]
+pub(crate) struct MachineRegistry {
+  machines: BTreeMap<u32, ProgramExecutor>,
+}
+
+impl MachineRegistry {
+  fn get_mut(&mut self, id: u64) -> Option<&mut ProgramExecutor> {
+    self.machines.get_mut(&u32::try_from(id).ok()?)
+  }
+
+  pub(crate) fn is_empty(&self) -> bool {
+    self.machines.is_empty()
+  }
+
+  pub(crate) fn clear(&mut self) {
+    self.machines.clear();
+  }
+}
+
+impl ProgramExecutor {
+  /// The PC execution continues from, past a host call the machine
+  /// stopped on
+  fn resume_program_counter(&self) -> u32 {
+    self
+      .instance
+      .next_program_counter()
+      .or(self.instance.program_counter())
+      .map_or(0, |pc| pc.0)
+  }
+
+  /// Applies a `pages` access mode to `[address, address + size)`
+  fn set_page_mode(
+    &mut self,
+    address: u32,
+    size: u32,
+    mode: u64,
+  ) -> Result<(), ()> {
+    if size == 0 {
+      return Ok(());
+    }
+
+    if mode == 0 {
+      self.instance.free_pages(address, size).map_err(|_| ())?;
+      self.page_map.unmap(address, size);
+      return Ok(());
+    }
+
+    // Modes 3 and 4 keep the contents, 1 and 2 zero them
+    let mut data = vec![0; size as usize];
+    if mode > 2 {
+      self
+        .instance
+        .read_memory_into(address, &mut data[..])
+        .map_err(|_| ())?;
+    }
+    self.instance.zero_memory(address, size).map_err(|_| ())?;
+    self.instance.write_memory(address, &data).map_err(|_| ())?;
+
+    let is_writable = mode % 2 == 0;
+    if !is_writable {
+      self
+        .instance
+        .protect_memory(address, size)
+        .map_err(|_| ())?;
+    }
+    self.page_map.map(
+      address,
+      size,
+      PageInfo {
+        is_writable,
+        is_dynamic: false,
+      },
+    );
+    Ok(())
+  }
+
+  /// Ω_M, creates a machine from the program at ω7 with length ω8 that
+  /// starts at ω9
+  pub(crate) fn machine_host_call(&mut self) -> Option<u64> {
+    let [program_address, program_len, entry, ..] = registers(self);
+    let program = read_guest(self, program_address, program_len)?;
+
+    let Some(mut machine) = JamProgram::parse(&program).ok().and_then(|p| {
+      Self::from_parts(&p.to_polkavm_blob(), &[], [0; 13], 0).ok()
+    }) else {
+      return Some(return_code::HUH);
+    };
+    // An entry beyond the code panics on the first invoke
+    machine.set_next_program_counter(u32::try_from(entry).unwrap_or(u32::MAX));
+
+    let machines = &mut self.machines.machines;
+    let id = (0..=u32::MAX).find(|id| !machines.contains_key(id))?;
+    machines.insert(id, machine);
+    Some(id as u64)
+  }
+
+  /// Ω_P, copies ω10 bytes from ω9 in machine ω7 to ω8
+  pub(crate) fn peek_host_call(&mut self) -> Option<u64> {
+    let [id, output, source, len, ..] = registers(self);
+    let output = u32::try_from(output).ok()?;
+    if !self
+      .page_map
+      .is_accessible(output, u32::try_from(len).ok()?, true)
+    {
+      return None;
+    }
+
+    let Some(machine) = self.machines.get_mut(id) else {
+      return Some(return_code::WHO);
+    };
+    let Some(data) = read_guest(machine, source, len) else {
+      return Some(return_code::OOB);
+    };
+    self.write_memory(output, &data).ok()?;
+    Some(return_code::OK)
+  }
+
+  /// Ω_O, copies ω10 bytes from ω8 to ω9 in machine ω7
+  pub(crate) fn poke_host_call(&mut self) -> Option<u64> {
+    let [id, source, output, len, ..] = registers(self);
+    let data = read_guest(self, source, len)?;
+
+    let Some(machine) = self.machines.get_mut(id) else {
+      return Some(return_code::WHO);
+    };
+    let written = u32::try_from(output)
+      .ok()
+      .and_then(|output| machine.write_memory(output, &data).ok());
+    Some(written.map_or(return_code::OOB, |_| return_code::OK))
+  }
+
+  /// Ω_Z, sets the access of ω9 pages from page ω8 in machine ω7 to mode ω10
+  pub(crate) fn pages_host_call(&mut self) -> Option<u64> {
+    let [id, page, count, mode, ..] = registers(self);
+    let Some(machine) = self.machines.get_mut(id) else {
+      return Some(return_code::WHO);
+    };
+
+    let page_limit = (1u64 << 32) / PAGE_SIZE as u64;
+    if mode > 4
+      || page < 16
+      || page.checked_add(count).is_none_or(|end| end >= page_limit)
+    {
+      return Some(return_code::HUH);
+    }
+
+    let address = page as u32 * PAGE_SIZE;
+    let size = count as u32 * PAGE_SIZE;
+    if mode > 2 && !machine.page_map.is_accessible(address, size, false) {
+      return Some(return_code::HUH);
+    }
+
+    machine.set_page_mode(address, size, mode).ok()?;
+    Some(return_code::OK)
+  }
+
+  /// Ω_K, runs machine ω7 with the gas and registers stored at ω8 and
+  /// writes them back, the exit reason goes to ω7 and its detail to ω8
+  ///
+  /// The machine runs on what is left of this executor's run limits and
+  /// stops on its interrupts. When it is stopped that way its gas and
+  /// registers are still written back, so making the host call again
+  /// continues the machine where it stopped.
+  pub(crate) fn invoke_host_call(
+    &mut self,
+  ) -> Result<Option<u64>, Interrupted> {
+    let [id, state_address, ..] = registers(self);
+    let Some(mut state) = read_guest(self, state_address, INVOKE_STATE_SIZE)
+    else {
+      return Ok(None);
+    };
+    let state_address = state_address as u32;
+    if !self.page_map.is_accessible(
+      state_address,
+      INVOKE_STATE_SIZE as u32,
+      true,
+    ) {
+      return Ok(None);
+    }
+
+    let Some(machine) = self.machines.get_mut(id) else {
+      return Ok(Some(return_code::WHO));
+    };
+
+    let word = |state: &[u8], i: usize| {
+      u64::from_le_bytes(state[i * 8..i * 8 + 8].try_into().unwrap())
+    };
+    machine.set_gas(word(&state, 0) as i64);
+    for (i, reg) in Reg::ALL.into_iter().enumerate() {
+      machine.instance.set_reg(reg, word(&state, i + 1));
+    }
+
+    // Faults and missing gas can be resolved by the parent, the machine
+    // retries the instruction
+    if matches!(
+      machine.current_status,
+      ExecutionStatus::Segfault | ExecutionStatus::OutOfGas
+    ) {
+      machine.current_status = ExecutionStatus::Running;
+      machine.pending_page_fault = None;
+    }
+    let run = self
+      .limits
+      .run_child(machine, |machine| machine.host_call.is_some());
+
+    let (reason, detail) = match machine.current_status {
+      ExecutionStatus::Success => (exit_reason::HALT, None),
+      ExecutionStatus::Segfault => {
+        (exit_reason::FAULT, Some(machine.segfault_address as u64))
+      }
+      ExecutionStatus::HostCall => {
+        (exit_reason::HOST, machine.host_call.map(u64::from))
+      }
+      ExecutionStatus::OutOfGas => (exit_reason::OOG, None),
+      ExecutionStatus::Trap
+      | ExecutionStatus::InstanceRunError
+      | ExecutionStatus::Running
+      | ExecutionStatus::Interrupted => (exit_reason::PANIC, None),
+    };
+
+    state[..8].copy_from_slice(&machine.gas().to_le_bytes());
+    for (i, reg) in Reg::ALL.into_iter().enumerate() {
+      state[8 + i * 8..16 + i * 8]
+        .copy_from_slice(&machine.instance.reg(reg).to_le_bytes());
+    }
+    if self.write_memory(state_address, &state).is_err() {
+      return Ok(None);
+    }
+    run?;
+
+    if let Some(detail) = detail {
+      self.instance.set_reg(Reg::A1, detail);
+    }
+    Ok(Some(reason))
+  }
+
+  /// Ω_X, removes machine ω7 and returns its PC
+  pub(crate) fn expunge_host_call(&mut self) -> Option<u64> {
+    let [id, ..] = registers(self);
+    let machine = u32::try_from(id)
+      .ok()
+      .and_then(|id| self.machines.machines.remove(&id));
+    Some(machine.map_or(return_code::WHO, |machine| {
+      machine.resume_program_counter() as u64
+    }))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    thread,
+    time::{Duration, Instant},
+  };
+
+  use polkavm_common::{program::asm, writer::ProgramBlobBuilder};
+
+  use super::*;
+  use crate::{snapshot::SnapshotError, MemoryPage};
+
+  const DATA: u32 = 0x20000;
+
+  /// `ecalli 42` followed by `trap` in the JAM program format
+  const CHILD_PROGRAM: [u8; 7] = [0, 0, 3, 10, 42, 0, 0b101];
+
+  /// `jump 0` in the JAM program format
+  const LOOP_PROGRAM: [u8; 5] = [0, 0, 1, 40, 0b1];
+
+  fn call(
+    executor: &mut ProgramExecutor,
+    host_call: fn(&mut ProgramExecutor) -> Option<u64>,
+    args: &[u64],
+  ) -> Option<u64> {
+    for (i, &arg) in args.iter().enumerate() {
+      executor.set_register(7 + i as u32, arg).unwrap();
+    }
+    host_call(executor)
+  }
+
+  /// Parent executor with `memory` mapped at `DATA`
+  fn create_parent(memory: &mut [u8]) -> ProgramExecutor {
+    let mut builder = ProgramBlobBuilder::new_64bit();
+    builder.add_export_by_basic_block(0, b"main");
+    builder.set_code(&[asm::ret()], &[]);
+    let program = builder.into_vec();
+
+    let page = MemoryPage {
+      address: DATA,
+      data: memory.as_mut_ptr(),
+      size: memory.len(),
+      is_writable: true,
+    };
+    let registers = [0u64; 13];
+
+    unsafe {
+      ProgramExecutor::new(
+        program.as_ptr(),
+        program.len(),
+        &page,
+        1,
+        registers.as_ptr(),
+        10000,
+      )
+      .expect("Failed to create executor")
+    }
+  }
+
+  #[test]
+  fn test_inner_machine_lifecycle() {
+    let mut memory = vec![0u8; 4096];
+    memory[..CHILD_PROGRAM.len()].copy_from_slice(&CHILD_PROGRAM);
+    memory[0x100..0x104].copy_from_slice(&[1, 2, 3, 4]);
+    // Gas for the child at the start of the invoke state
+    memory[0x200..0x208].copy_from_slice(&1000u64.to_le_bytes());
+    let mut parent = create_parent(&mut memory);
+
+    let snapshot = parent.snapshot().unwrap();
+    let data = DATA as u64;
+    let machine = ProgramExecutor::machine_host_call;
+    let id = call(&mut parent, machine, &[data, 7, 0]).unwrap();
+    assert_eq!(id, 0);
+    assert_eq!(parent.snapshot().err(), Some(SnapshotError::MachinesExist));
+    assert_eq!(
+      call(&mut parent, machine, &[data, 2, 0]),
+      Some(return_code::HUH)
+    );
+
+    // Poking unmapped child memory is out of bounds until pages are mapped
+    let poke = ProgramExecutor::poke_host_call;
+    assert_eq!(
+      call(&mut parent, poke, &[id, data + 0x100, 0x10000, 4]),
+      Some(return_code::OOB)
+    );
+    let pages = ProgramExecutor::pages_host_call;
+    assert_eq!(
+      call(&mut parent, pages, &[id, 16, 1, 2]),
+      Some(return_code::OK)
+    );
+    assert_eq!(
+      call(&mut parent, poke, &[id, data + 0x100, 0x10000, 4]),
+      Some(return_code::OK)
+    );
+
+    let peek = ProgramExecutor::peek_host_call;
+    assert_eq!(
+      call(&mut parent, peek, &[id, data + 0x300, 0x10000, 4]),
+      Some(return_code::OK)
+    );
+    let mut peeked = [0u8; 4];
+    parent.read_memory_into(DATA + 0x300, &mut peeked).unwrap();
+    assert_eq!(peeked, [1, 2, 3, 4]);
+
+    let invoke = |executor: &mut ProgramExecutor| {
+      executor.invoke_host_call().expect("Invoke was interrupted")
+    };
+    assert_eq!(
+      call(&mut parent, invoke, &[id, data + 0x200]),
+      Some(exit_reason::HOST)
+    );
+    assert_eq!(parent.register(Reg::A1 as u32), Ok(42));
+
+    let expunge = ProgramExecutor::expunge_host_call;
+    assert_eq!(call(&mut parent, expunge, &[id]), Some(2));
+    assert_eq!(call(&mut parent, expunge, &[id]), Some(return_code::WHO));
+
+    // Restoring drops machines created after the snapshot
+    call(&mut parent, machine, &[data, 7, 0]).unwrap();
+    parent.restore(&snapshot).unwrap();
+    assert!(parent.snapshot().is_ok());
+  }
+
+  #[test]
+  fn test_invoke_runs_on_parent_limits() {
+    let mut memory = vec![0u8; 4096];
+    memory[..LOOP_PROGRAM.len()].copy_from_slice(&LOOP_PROGRAM);
+    memory[0x200..0x208].copy_from_slice(&(i64::MAX as u64).to_le_bytes());
+    let mut parent = create_parent(&mut memory);
+    parent.set_step_limit(Some(100));
+
+    let data = DATA as u64;
+    let id = call(
+      &mut parent,
+      ProgramExecutor::machine_host_call,
+      &[data, LOOP_PROGRAM.len() as u64, 0],
+    )
+    .unwrap();
+
+    parent.set_register(7, id).unwrap();
+    parent.set_register(8, data + 0x200).unwrap();
+    assert!(parent.invoke_host_call().is_err());
+    let mut gas = [0u8; 8];
+    parent.read_memory_into(DATA + 0x200, &mut gas).unwrap();
+    assert!(i64::from_le_bytes(gas) < i64::MAX);
+
+    // An interrupt of the parent stops the machine as well, the time limit
+    // only keeps the test from hanging if it is lost
+    parent.set_step_limit(None);
+    parent.set_time_limit(Some(Duration::from_secs(10)));
+    let handle = parent.interrupt_handle();
+    let start = Instant::now();
+    let interrupter = thread::spawn(move || {
+      thread::sleep(Duration::from_millis(20));
+      handle.interrupt();
+    });
+    assert!(parent.invoke_host_call().is_err());
+    interrupter.join().unwrap();
+    assert!(start.elapsed() < Duration::from_secs(10));
+  }
+}
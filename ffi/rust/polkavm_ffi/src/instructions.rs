@@ -0,0 +1,323 @@
+//! Decoded view of a program's instructions, keyed by program counter
+
+use std::collections::BTreeMap;
+
+use polkavm::{ProgramBlob, RawInstance};
+use polkavm_common::program::{
+  Instruction, InstructionSet, RawReg, ISA32_V1, ISA64_V1,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct DecodedInstruction {
+  pub(crate) kind: Instruction,
+  pub(crate) next_pc: u32,
+}
+
+/// A memory access performed by an instruction
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) struct MemoryAccess {
+  pub(crate) address: u32,
+  pub(crate) size: u32,
+  pub(crate) is_write: bool,
+}
+
+/// Register and immediate operands of an instruction, in operand order
+///
+/// Branch and jump targets are reported as immediates.
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub(crate) struct Operands {
+  pub(crate) registers: [u8; 3],
+  pub(crate) register_count: u8,
+  pub(crate) immediates: [u64; 2],
+  pub(crate) immediate_count: u8,
+}
+
+impl Operands {
+  fn new(registers: &[RawReg], immediates: &[u64]) -> Self {
+    let mut operands = Self {
+      register_count: registers.len() as u8,
+      immediate_count: immediates.len() as u8,
+      ..Self::default()
+    };
+    for (slot, reg) in operands.registers.iter_mut().zip(registers) {
+      *slot = reg.get() as u8;
+    }
+    operands.immediates[..immediates.len()].copy_from_slice(immediates);
+    operands
+  }
+}
+
+#[derive(Debug, Default, Clone)]
+pub(crate) struct InstructionIndex {
+  instructions: BTreeMap<u32, DecodedInstruction>,
+}
+
+impl InstructionIndex {
+  pub(crate) fn new(blob: &ProgramBlob) -> Self {
+    if blob.is_64_bit() {
+      Self::decode(blob, ISA64_V1)
+    } else {
+      Self::decode(blob, ISA32_V1)
+    }
+  }
+
+  fn decode<I: InstructionSet>(blob: &ProgramBlob, isa: I) -> Self {
+    let instructions = blob
+      .instructions(isa)
+      .map(|instruction| {
+        (
+          instruction.offset.0,
+          DecodedInstruction {
+            kind: instruction.kind,
+            next_pc: instruction.next_offset.0,
+          },
+        )
+      })
+      .collect();
+
+    Self { instructions }
+  }
+
+  pub(crate) fn get(&self, pc: u32) -> Option<&DecodedInstruction> {
+    self.instructions.get(&pc)
+  }
+
+  /// Returns the PCs at which basic blocks start, in ascending order
+  pub(crate) fn basic_block_starts(&self) -> Vec<u32> {
+    let mut starts = Vec::new();
+    let mut starts_block = true;
+    for (&pc, instruction) in &self.instructions {
+      if starts_block {
+        starts.push(pc);
+      }
+      starts_block = instruction.kind.opcode().starts_new_basic_block();
+    }
+    starts
+  }
+
+  pub(crate) fn iter(
+    &self,
+  ) -> impl Iterator<Item = (u32, &DecodedInstruction)> + '_ {
+    self
+      .instructions
+      .iter()
+      .map(|(&pc, instruction)| (pc, instruction))
+  }
+}
+
+/// Returns the memory access `instruction` performs given the current
+/// register values
+pub(crate) fn memory_access(
+  instruction: &Instruction,
+  instance: &RawInstance,
+) -> Option<MemoryAccess> {
+  use Instruction::*;
+
+  let indirect = |base: polkavm_common::program::RawReg, offset: u32| {
+    (instance.reg(base.get()) as u32).wrapping_add(offset)
+  };
+  let access = |address: u32, size: u32, is_write: bool| {
+    Some(MemoryAccess {
+      address,
+      size,
+      is_write,
+    })
+  };
+
+  match *instruction {
+    load_u8(_, address) | load_i8(_, address) => access(address, 1, false),
+    load_u16(_, address) | load_i16(_, address) => access(address, 2, false),
+    load_u32(_, address) | load_i32(_, address) => access(address, 4, false),
+    load_u64(_, address) => access(address, 8, false),
+    store_u8(_, address) | store_imm_u8(address, _) => access(address, 1, true),
+    store_u16(_, address) | store_imm_u16(address, _) => {
+      access(address, 2, true)
+    }
+    store_u32(_, address) | store_imm_u32(address, _) => {
+      access(address, 4, true)
+    }
+    store_u64(_, address) | store_imm_u64(address, _) => {
+      access(address, 8, true)
+    }
+    load_indirect_u8(_, base, offset) | load_indirect_i8(_, base, offset) => {
+      access(indirect(base, offset), 1, false)
+    }
+    load_indirect_u16(_, base, offset) | load_indirect_i16(_, base, offset) => {
+      access(indirect(base, offset), 2, false)
+    }
+    load_indirect_u32(_, base, offset) | load_indirect_i32(_, base, offset) => {
+      access(indirect(base, offset), 4, false)
+    }
+    load_indirect_u64(_, base, offset) => {
+      access(indirect(base, offset), 8, false)
+    }
+    store_indirect_u8(_, base, offset)
+    | store_imm_indirect_u8(base, offset, _) => {
+      access(indirect(base, offset), 1, true)
+    }
+    store_indirect_u16(_, base, offset)
+    | store_imm_indirect_u16(base, offset, _) => {
+      access(indirect(base, offset), 2, true)
+    }
+    store_indirect_u32(_, base, offset)
+    | store_imm_indirect_u32(base, offset, _) => {
+      access(indirect(base, offset), 4, true)
+    }
+    store_indirect_u64(_, base, offset)
+    | store_imm_indirect_u64(base, offset, _) => {
+      access(indirect(base, offset), 8, true)
+    }
+    _ => None,
+  }
+}
+
+/// Returns the decoded operands of `instruction`
+pub(crate) fn operands(instruction: &Instruction) -> Operands {
+  use Instruction::*;
+
+  match *instruction {
+    ecalli(imm) | jump(imm) => Operands::new(&[], &[imm.into()]),
+    load_imm64(reg, imm) => Operands::new(&[reg], &[imm]),
+    store_imm_u8(a, b)
+    | store_imm_u16(a, b)
+    | store_imm_u32(a, b)
+    | store_imm_u64(a, b) => Operands::new(&[], &[a.into(), b.into()]),
+    jump_indirect(reg, imm)
+    | load_imm(reg, imm)
+    | load_u8(reg, imm)
+    | load_i8(reg, imm)
+    | load_u16(reg, imm)
+    | load_i16(reg, imm)
+    | load_u32(reg, imm)
+    | load_i32(reg, imm)
+    | load_u64(reg, imm)
+    | store_u8(reg, imm)
+    | store_u16(reg, imm)
+    | store_u32(reg, imm)
+    | store_u64(reg, imm) => Operands::new(&[reg], &[imm.into()]),
+    store_imm_indirect_u8(reg, a, b)
+    | store_imm_indirect_u16(reg, a, b)
+    | store_imm_indirect_u32(reg, a, b)
+    | store_imm_indirect_u64(reg, a, b)
+    | load_imm_and_jump(reg, a, b)
+    | branch_eq_imm(reg, a, b)
+    | branch_not_eq_imm(reg, a, b)
+    | branch_less_unsigned_imm(reg, a, b)
+    | branch_less_signed_imm(reg, a, b)
+    | branch_less_or_equal_unsigned_imm(reg, a, b)
+    | branch_less_or_equal_signed_imm(reg, a, b)
+    | branch_greater_or_equal_unsigned_imm(reg, a, b)
+    | branch_greater_or_equal_signed_imm(reg, a, b)
+    | branch_greater_unsigned_imm(reg, a, b)
+    | branch_greater_signed_imm(reg, a, b) => {
+      Operands::new(&[reg], &[a.into(), b.into()])
+    }
+    move_reg(a, b)
+    | sbrk(a, b)
+    | count_set_bits_32(a, b)
+    | count_set_bits_64(a, b)
+    | count_leading_zero_bits_32(a, b)
+    | count_leading_zero_bits_64(a, b)
+    | count_trailing_zero_bits_32(a, b)
+    | count_trailing_zero_bits_64(a, b)
+    | sign_extend_8(a, b)
+    | sign_extend_16(a, b)
+    | zero_extend_16(a, b)
+    | reverse_byte(a, b) => Operands::new(&[a, b], &[]),
+    store_indirect_u8(a, b, imm)
+    | store_indirect_u16(a, b, imm)
+    | store_indirect_u32(a, b, imm)
+    | store_indirect_u64(a, b, imm)
+    | load_indirect_u8(a, b, imm)
+    | load_indirect_i8(a, b, imm)
+    | load_indirect_u16(a, b, imm)
+    | load_indirect_i16(a, b, imm)
+    | load_indirect_u32(a, b, imm)
+    | load_indirect_i32(a, b, imm)
+    | load_indirect_u64(a, b, imm)
+    | add_imm_32(a, b, imm)
+    | add_imm_64(a, b, imm)
+    | and_imm(a, b, imm)
+    | xor_imm(a, b, imm)
+    | or_imm(a, b, imm)
+    | mul_imm_32(a, b, imm)
+    | mul_imm_64(a, b, imm)
+    | set_less_than_unsigned_imm(a, b, imm)
+    | set_less_than_signed_imm(a, b, imm)
+    | set_greater_than_unsigned_imm(a, b, imm)
+    | set_greater_than_signed_imm(a, b, imm)
+    | shift_logical_left_imm_32(a, b, imm)
+    | shift_logical_left_imm_64(a, b, imm)
+    | shift_logical_right_imm_32(a, b, imm)
+    | shift_logical_right_imm_64(a, b, imm)
+    | shift_arithmetic_right_imm_32(a, b, imm)
+    | shift_arithmetic_right_imm_64(a, b, imm)
+    | shift_logical_left_imm_alt_32(a, b, imm)
+    | shift_logical_left_imm_alt_64(a, b, imm)
+    | shift_logical_right_imm_alt_32(a, b, imm)
+    | shift_logical_right_imm_alt_64(a, b, imm)
+    | shift_arithmetic_right_imm_alt_32(a, b, imm)
+    | shift_arithmetic_right_imm_alt_64(a, b, imm)
+    | negate_and_add_imm_32(a, b, imm)
+    | negate_and_add_imm_64(a, b, imm)
+    | cmov_if_zero_imm(a, b, imm)
+    | cmov_if_not_zero_imm(a, b, imm)
+    | rotate_right_imm_32(a, b, imm)
+    | rotate_right_imm_64(a, b, imm)
+    | rotate_right_imm_alt_32(a, b, imm)
+    | rotate_right_imm_alt_64(a, b, imm)
+    | branch_eq(a, b, imm)
+    | branch_not_eq(a, b, imm)
+    | branch_less_unsigned(a, b, imm)
+    | branch_less_signed(a, b, imm)
+    | branch_greater_or_equal_unsigned(a, b, imm)
+    | branch_greater_or_equal_signed(a, b, imm) => {
+      Operands::new(&[a, b], &[imm.into()])
+    }
+    load_imm_and_jump_indirect(a, b, imm, offset) => {
+      Operands::new(&[a, b], &[imm.into(), offset.into()])
+    }
+    add_32(a, b, c)
+    | add_64(a, b, c)
+    | sub_32(a, b, c)
+    | sub_64(a, b, c)
+    | and(a, b, c)
+    | xor(a, b, c)
+    | or(a, b, c)
+    | and_inverted(a, b, c)
+    | or_inverted(a, b, c)
+    | xnor(a, b, c)
+    | mul_32(a, b, c)
+    | mul_64(a, b, c)
+    | mul_upper_signed_signed(a, b, c)
+    | mul_upper_unsigned_unsigned(a, b, c)
+    | mul_upper_signed_unsigned(a, b, c)
+    | set_less_than_unsigned(a, b, c)
+    | set_less_than_signed(a, b, c)
+    | shift_logical_left_32(a, b, c)
+    | shift_logical_left_64(a, b, c)
+    | shift_logical_right_32(a, b, c)
+    | shift_logical_right_64(a, b, c)
+    | shift_arithmetic_right_32(a, b, c)
+    | shift_arithmetic_right_64(a, b, c)
+    | div_unsigned_32(a, b, c)
+    | div_unsigned_64(a, b, c)
+    | div_signed_32(a, b, c)
+    | div_signed_64(a, b, c)
+    | rem_unsigned_32(a, b, c)
+    | rem_unsigned_64(a, b, c)
+    | rem_signed_32(a, b, c)
+    | rem_signed_64(a, b, c)
+    | cmov_if_zero(a, b, c)
+    | cmov_if_not_zero(a, b, c)
+    | maximum(a, b, c)
+    | maximum_unsigned(a, b, c)
+    | minimum(a, b, c)
+    | minimum_unsigned(a, b, c)
+    | rotate_left_32(a, b, c)
+    | rotate_left_64(a, b, c)
+    | rotate_right_32(a, b, c)
+    | rotate_right_64(a, b, c) => Operands::new(&[a, b, c], &[]),
+    _ => Operands::default(),
+  }
+}
@@ -1,13 +1,44 @@
-use std::mem;
+use std::collections::BTreeSet;
 use std::ptr;
 use std::slice;
-use std::sync::Once;
+use std::sync::{Arc, Once};
 
 use polkavm::RawInstance;
-use polkavm::{
-  BackendKind, Config, Engine, GasMeteringKind, InterruptKind, Module,
-  ModuleConfig, ProgramBlob, ProgramCounter, Reg,
-};
+use polkavm::{InterruptKind, ProgramCounter, Reg};
+
+pub mod batch;
+pub mod blob;
+pub mod breakpoints;
+pub mod coverage;
+#[cfg(any(test, feature = "fuzzing"))]
+pub mod differential;
+pub mod disassembly;
+pub mod entry_point;
+pub mod gas;
+pub mod host_calls;
+pub mod inner_machine;
+pub mod instructions;
+pub mod limits;
+pub mod memory_map;
+pub mod module_cache;
+pub mod paging;
+pub mod replay;
+pub mod snapshot;
+pub mod spi;
+pub mod state;
+#[cfg(any(test, feature = "test-vectors"))]
+pub mod test_vector;
+pub mod trace;
+
+use coverage::PcCoverage;
+use inner_machine::MachineRegistry;
+use instructions::InstructionIndex;
+use limits::RunLimits;
+use memory_map::{PageInfo, PageMap};
+use module_cache::{Backend, CachedModule};
+use paging::PendingPageFault;
+use replay::HostCallRecorder;
+use trace::StepTrace;
 
 static INIT: Once = Once::new();
 
@@ -28,6 +59,7 @@ pub enum InitializationError {
   ModuleError = 3,
   InstantiationError = 4,
   MemoryError = 5,
+  ProgramFormatError = 6,
 }
 
 #[repr(C)]
@@ -39,6 +71,10 @@ pub enum ExecutionStatus {
   Segfault = 3,
   InstanceRunError = 4,
   Running = 5,
+  /// Stopped by an interrupt or a run limit, execution can be continued
+  Interrupted = 6,
+  /// Stopped on an `ecalli`, execution continues after it on the next step
+  HostCall = 7,
 }
 
 #[repr(C)]
@@ -51,13 +87,111 @@ pub struct ExecutionResult {
   registers: [u64; 13],
   gas_remaining: i64,
   segfault_address: u32,
+  trace: StepTrace,
+}
+
+/// A memory range mapped into the executor at creation time
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct MemoryRegion {
+  pub(crate) address: u32,
+  pub(crate) size: usize,
+  pub(crate) is_writable: bool,
 }
 
 pub struct ProgramExecutor {
   instance: RawInstance,
-  initial_pages: Vec<MemoryPage>,
+  initial_pages: Vec<MemoryRegion>,
   current_status: ExecutionStatus,
   segfault_address: u32,
+  pending_page_fault: Option<PendingPageFault>,
+  page_map: PageMap,
+  instructions: Arc<InstructionIndex>,
+  /// PC of the instruction the next run executes, known only while stopped
+  /// on a step interrupt
+  step_pc: Option<u32>,
+  /// Set by the first step, the entry point is fixed from then on
+  started: bool,
+  /// Set when restored to a state without a next PC, which the instance
+  /// cannot be put back into, until a PC is set
+  pc_unset: bool,
+  last_trace: StepTrace,
+  /// Memory of the last execution result, borrowed by the caller
+  result_memory: Vec<Vec<u8>>,
+  result_pages: ResultPages,
+  breakpoints: BTreeSet<u32>,
+  limits: RunLimits,
+  /// Index of the host call the executor is stopped on
+  host_call: Option<u32>,
+  machines: MachineRegistry,
+  recorder: Option<HostCallRecorder>,
+  pc_coverage: PcCoverage,
+}
+
+/// Pages of the last execution result, pointing into `result_memory`
+#[derive(Debug, Default)]
+struct ResultPages(Vec<MemoryPage>);
+
+// SAFETY: the pages point into the buffers of `result_memory`, owned by the
+// same executor, so they move along with it. Heap buffers keep their
+// address when the executor is moved to another thread.
+unsafe impl Send for ResultPages {}
+
+impl ResultPages {
+  /// Refills the pages with `regions`, reading each into its buffer of
+  /// `memory`. Regions that fail to read are left out of the pages.
+  ///
+  /// The buffers are reused between calls, the previous pages are dangling
+  /// once this returns.
+  fn collect(
+    &mut self,
+    memory: &mut Vec<Vec<u8>>,
+    regions: &[MemoryRegion],
+    mut read: impl FnMut(u32, &mut [u8]) -> bool,
+  ) {
+    self.0.clear();
+    memory.resize_with(regions.len(), Vec::new);
+    for (region, buffer) in regions.iter().zip(memory.iter_mut()) {
+      buffer.resize(region.size, 0);
+      if read(region.address, &mut buffer[..]) {
+        self.0.push(MemoryPage {
+          address: region.address,
+          data: buffer.as_mut_ptr(),
+          size: region.size,
+          is_writable: region.is_writable,
+        });
+      }
+    }
+  }
+}
+
+/// Reads `page_count` pages from the caller as memory regions
+///
+/// # Safety
+///
+/// `pages` must point to `page_count` pages whose data is valid for reads
+/// of their size, for as long as the regions are used
+pub(crate) unsafe fn memory_regions<'a>(
+  pages: *const MemoryPage,
+  page_count: usize,
+) -> Vec<(MemoryRegion, &'a [u8])> {
+  let pages = if page_count == 0 {
+    &[][..]
+  } else {
+    slice::from_raw_parts(pages, page_count)
+  };
+  pages
+    .iter()
+    .map(|page| {
+      (
+        MemoryRegion {
+          address: page.address,
+          size: page.size,
+          is_writable: page.is_writable,
+        },
+        slice::from_raw_parts(page.data as *const u8, page.size),
+      )
+    })
+    .collect()
 }
 
 impl ProgramExecutor {
@@ -76,55 +210,85 @@ impl ProgramExecutor {
     initial_registers: *const u64,
     gas_limit: u64,
   ) -> Result<Self, InitializationError> {
-    // Initialize engine configuration
-    let mut config = Config::new();
-    config.set_backend(Some(BackendKind::Interpreter));
-    config.set_allow_dynamic_paging(true);
+    let raw_bytes = slice::from_raw_parts(bytecode, bytecode_len);
+    let regions = memory_regions(initial_pages, page_count);
 
-    // Initialize engine
-    let engine =
-      Engine::new(&config).map_err(|_| InitializationError::EngineError)?;
+    let mut registers = [0u64; 13];
+    registers.copy_from_slice(slice::from_raw_parts(initial_registers, 13));
 
-    // Parse program blob
-    let raw_bytes = slice::from_raw_parts(bytecode, bytecode_len);
-    let blob = ProgramBlob::parse(raw_bytes.to_vec().into())
-      .map_err(|_| InitializationError::ProgramError)?;
+    Self::from_parts(raw_bytes, &regions, registers, gas_limit)
+  }
 
-    // Configure and create module
-    let mut module_config = ModuleConfig::default();
-    module_config.set_strict(true);
-    module_config.set_gas_metering(Some(GasMeteringKind::Sync));
-    module_config.set_dynamic_paging(true);
-    module_config.set_step_tracing(true);
+  /// Creates a new program executor from a polkavm blob and memory regions
+  ///
+  /// Each region is zero filled before its initial data is written, the
+  /// data may be shorter than the region.
+  pub(crate) fn from_parts(
+    raw_bytes: &[u8],
+    regions: &[(MemoryRegion, &[u8])],
+    registers: [u64; 13],
+    gas_limit: u64,
+  ) -> Result<Self, InitializationError> {
+    Self::from_parts_with_backend(
+      raw_bytes,
+      regions,
+      registers,
+      gas_limit,
+      Backend::Interpreter,
+    )
+  }
 
-    let module = Module::from_blob(&engine, &module_config, blob)
-      .map_err(|_| InitializationError::ModuleError)?;
+  pub(crate) fn from_parts_with_backend(
+    raw_bytes: &[u8],
+    regions: &[(MemoryRegion, &[u8])],
+    registers: [u64; 13],
+    gas_limit: u64,
+    backend: Backend,
+  ) -> Result<Self, InitializationError> {
+    let CachedModule {
+      module,
+      instructions,
+    } = module_cache::module_for(raw_bytes, backend)?;
 
     // Instantiate module
     let mut instance = module
       .instantiate()
       .map_err(|_| InitializationError::InstantiationError)?;
 
-    // Store initial pages for later use
-    let pages = slice::from_raw_parts(initial_pages, page_count);
-    let initial_pages = pages.to_vec();
+    // Initialize memory regions
+    let mut page_map = PageMap::default();
+    for (region, data) in regions {
+      if data.len() > region.size {
+        return Err(InitializationError::MemoryError);
+      }
 
-    // Initialize memory pages
-    for page in pages {
-      let page_data = slice::from_raw_parts(page.data, page.size);
       instance
-        .write_memory(page.address, page_data)
+        .zero_memory(region.address, region.size as u32)
         .map_err(|_| InitializationError::MemoryError)?;
 
-      if !page.is_writable {
+      if !data.is_empty() {
         instance
-          .protect_memory(page.address, page.size as u32)
+          .write_memory(region.address, data)
           .map_err(|_| InitializationError::MemoryError)?;
       }
+
+      if !region.is_writable {
+        instance
+          .protect_memory(region.address, region.size as u32)
+          .map_err(|_| InitializationError::MemoryError)?;
+      }
+
+      page_map.map(
+        region.address,
+        region.size as u32,
+        PageInfo {
+          is_writable: region.is_writable,
+          is_dynamic: false,
+        },
+      );
     }
 
     // Set initial register values
-    let registers = slice::from_raw_parts(initial_registers, 13);
     for (i, &value) in registers.iter().enumerate() {
       if let Some(reg) = Reg::from_raw(i as u32) {
         instance.set_reg(reg, value);
@@ -137,34 +301,79 @@ impl ProgramExecutor {
 
     Ok(Self {
       instance,
-      initial_pages,
+      initial_pages: regions.iter().map(|(region, _)| *region).collect(),
       current_status: ExecutionStatus::Running,
       segfault_address: 0,
+      pending_page_fault: None,
+      page_map,
+      instructions,
+      step_pc: None,
+      started: false,
+      pc_unset: false,
+      last_trace: StepTrace::empty(),
+      result_memory: Vec::new(),
+      result_pages: ResultPages::default(),
+      breakpoints: BTreeSet::new(),
+      limits: RunLimits::default(),
+      host_call: None,
+      machines: MachineRegistry::default(),
+      recorder: None,
+      pc_coverage: PcCoverage::default(),
     })
   }
 
   /// Executes a single step of the program
   pub fn step(&mut self) -> ExecutionResult {
-    match self.instance.run() {
-      Ok(interrupt) => {
+    self.advance();
+    self.create_execution_result()
+  }
+
+  /// Executes a single step without collecting an execution result
+  pub fn advance(&mut self) {
+    self.finish_host_call_record();
+    let pending_trace = self.begin_step_trace();
+    self.started = true;
+    self.step_pc = None;
+    self.host_call = None;
+
+    // Without a next PC the run fails, as it would have on the instance
+    // the state was captured from
+    match (!self.pc_unset).then(|| self.instance.run()) {
+      Some(Ok(interrupt)) => {
         self.current_status = match interrupt {
           InterruptKind::Finished => ExecutionStatus::Success,
           InterruptKind::Trap => ExecutionStatus::Trap,
           InterruptKind::NotEnoughGas => ExecutionStatus::OutOfGas,
           InterruptKind::Segfault(sfault) => {
             self.segfault_address = sfault.page_address;
+            self.pending_page_fault = Some(PendingPageFault {
+              page_address: sfault.page_address,
+              page_size: sfault.page_size,
+            });
             ExecutionStatus::Segfault
           }
-          InterruptKind::Step => ExecutionStatus::Running,
-          InterruptKind::Ecalli(_) => ExecutionStatus::Running,
+          InterruptKind::Step => {
+            self.step_pc = self.instance.program_counter().map(|pc| pc.0);
+            ExecutionStatus::Running
+          }
+          InterruptKind::Ecalli(index) => {
+            self.host_call = Some(index);
+            self.begin_host_call_record(index);
+            ExecutionStatus::HostCall
+          }
         };
       }
-      Err(_) => {
+      Some(Err(_)) | None => {
         self.current_status = ExecutionStatus::InstanceRunError;
       }
     }
 
-    self.create_execution_result()
+    self.last_trace = self.finish_step_trace(pending_trace);
+  }
+
+  /// Steps until the program finishes or a run limit is reached
+  pub fn run_to_completion(&mut self) {
+    self.run_until_stopped(|_| false);
   }
 
   /// Returns true if the program has finished executing
@@ -180,27 +389,17 @@ impl ProgramExecutor {
   }
 
   /// Creates an execution result from the current state
-  fn create_execution_result(&self) -> ExecutionResult {
-    // Collect final memory state
-    let mut result_pages = Vec::with_capacity(self.initial_pages.len());
-    for page in &self.initial_pages {
-      if let Ok(mut page_data) =
-        self.instance.read_memory(page.address, page.size as u32)
-      {
-        let result_page = MemoryPage {
-          address: page.address,
-          data: page_data.as_mut_ptr(),
-          size: page.size,
-          is_writable: page.is_writable,
-        };
-        mem::forget(page_data); // Prevent deallocation
-        result_pages.push(result_page);
-      }
-    }
-
-    let pages_ptr = result_pages.as_mut_ptr();
-    let page_count = result_pages.len();
-    mem::forget(result_pages); // Prevent deallocation
+  ///
+  /// The pages point into buffers owned by the executor, they stay valid
+  /// until the next step or until the executor is freed.
+  fn create_execution_result(&mut self) -> ExecutionResult {
+    // Collect final memory state, reusing the buffers of the last result
+    let instance = &mut self.instance;
+    self.result_pages.collect(
+      &mut self.result_memory,
+      &self.initial_pages,
+      |address, buffer| instance.read_memory_into(address, buffer).is_ok(),
+    );
 
     // Collect register values
     let mut registers = [0u64; 13];
@@ -217,11 +416,12 @@ impl ProgramExecutor {
         .program_counter()
         .unwrap_or(ProgramCounter(0))
         .0,
-      pages: pages_ptr,
-      page_count,
+      pages: self.result_pages.0.as_mut_ptr(),
+      page_count: self.result_pages.0.len(),
       registers,
       gas_remaining: self.instance.gas(),
       segfault_address: self.segfault_address,
+      trace: self.last_trace,
     }
   }
 }
@@ -267,7 +467,8 @@ pub unsafe extern "C" fn create_executor(
 ///
 /// This function is unsafe because it:
 /// - Accepts a raw pointer as input
-/// - Returns unmanaged memory that must be freed
+/// - Returns pages borrowed from the executor, they must not be used after
+///   the next step or after the executor is freed
 #[no_mangle]
 pub unsafe extern "C" fn step_executor(
   executor: *mut ProgramExecutor,
@@ -301,24 +502,6 @@ pub unsafe extern "C" fn free_executor(executor: *mut ProgramExecutor) {
   }
 }
 
-/// Frees memory allocated during execution
-///
-/// # Safety
-///
-/// This function is unsafe because it:
-/// - Deallocates memory based on raw pointers
-/// - Must be called exactly once for each ExecutionResult
-#[no_mangle]
-pub unsafe extern "C" fn free_execution_result(result: ExecutionResult) {
-  if !result.pages.is_null() {
-    let pages = slice::from_raw_parts_mut(result.pages, result.page_count);
-    for page in pages {
-      Vec::from_raw_parts(page.data, page.size, page.size);
-    }
-    Vec::from_raw_parts(result.pages, result.page_count, result.page_count);
-  }
-}
-
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -373,6 +556,7 @@ mod tests {
         registers: [0; 13],
         gas_remaining: 0,
         segfault_address: 0,
+        trace: StepTrace::empty(),
       };
 
       while !executor.is_finished() {
@@ -387,10 +571,190 @@ mod tests {
       let data = slice::from_raw_parts(first_page.data, 4);
       assert_eq!(u32::from_le_bytes(data.try_into().unwrap()), 0x12345678);
       assert_eq!(last_result.registers[2], 0xdeadbeef);
+    }
+  }
+
+  /// Result page handling without a polkavm instance, runs under Miri
+  mod result_pages {
+    use super::*;
 
-      free_execution_result(last_result);
+    fn region(address: u32, size: usize) -> MemoryRegion {
+      MemoryRegion {
+        address,
+        size,
+        is_writable: true,
+      }
     }
 
-    mem::forget(memory);
+    fn page_data(page: &MemoryPage) -> &[u8] {
+      unsafe { slice::from_raw_parts(page.data, page.size) }
+    }
+
+    #[test]
+    fn test_result_pages_point_into_buffers() {
+      let mut pages = ResultPages::default();
+      let mut memory = Vec::new();
+      let regions = [region(0x10000, 16), region(0x20000, 8)];
+
+      pages.collect(&mut memory, &regions, |address, buffer| {
+        buffer.fill((address >> 16) as u8);
+        true
+      });
+
+      assert_eq!(pages.0.len(), 2);
+      assert_eq!(pages.0[0].address, 0x10000);
+      assert_eq!(page_data(&pages.0[0]), &[1u8; 16]);
+      assert_eq!(pages.0[1].address, 0x20000);
+      assert_eq!(page_data(&pages.0[1]), &[2u8; 8]);
+      assert_eq!(pages.0[1].data, memory[1].as_mut_ptr());
+    }
+
+    #[test]
+    fn test_result_pages_skip_failed_reads() {
+      let mut pages = ResultPages::default();
+      let mut memory = Vec::new();
+      let regions = [region(0x10000, 4), region(0x20000, 4)];
+
+      pages.collect(&mut memory, &regions, |address, _| address != 0x10000);
+
+      assert_eq!(pages.0.len(), 1);
+      assert_eq!(pages.0[0].address, 0x20000);
+      assert_eq!(pages.0[0].data, memory[1].as_mut_ptr());
+    }
+
+    #[test]
+    fn test_result_pages_reuse_buffers() {
+      let mut pages = ResultPages::default();
+      let mut memory = Vec::new();
+      let regions = [region(0x10000, 32)];
+
+      pages.collect(&mut memory, &regions, |_, buffer| {
+        buffer.fill(0xaa);
+        true
+      });
+      let first = pages.0[0].data;
+
+      pages.collect(&mut memory, &regions, |_, buffer| {
+        buffer.fill(0xbb);
+        true
+      });
+
+      assert_eq!(pages.0.len(), 1);
+      assert_eq!(pages.0[0].data, first);
+      assert_eq!(page_data(&pages.0[0]), &[0xbbu8; 32]);
+    }
+
+    #[test]
+    fn test_result_pages_shrink_with_regions() {
+      let mut pages = ResultPages::default();
+      let mut memory = Vec::new();
+
+      pages.collect(
+        &mut memory,
+        &[region(0x10000, 8), region(0x20000, 8)],
+        |_, _| true,
+      );
+      pages.collect(&mut memory, &[region(0x30000, 4)], |_, buffer| {
+        buffer.fill(7);
+        true
+      });
+
+      assert_eq!(memory.len(), 1);
+      assert_eq!(pages.0.len(), 1);
+      assert_eq!(page_data(&pages.0[0]), &[7u8; 4]);
+    }
+  }
+
+  /// Lifecycle tests of the FFI surface, creating, stepping and freeing
+  /// executors through the exported functions only
+  mod lifecycle {
+    use super::*;
+
+    #[test]
+    fn lifecycle_result_pages_are_borrowed_until_next_step() {
+      let program = create_test_program();
+      let mut memory = vec![0xaau8; 4096];
+      let page = MemoryPage {
+        address: 0x20000,
+        data: memory.as_mut_ptr(),
+        size: 4096,
+        is_writable: true,
+      };
+      let registers = [0u64; 13];
+
+      unsafe {
+        let executor = create_executor(
+          program.as_ptr(),
+          program.len(),
+          &page,
+          1,
+          registers.as_ptr(),
+          10000,
+        );
+        assert!(!executor.is_null());
+
+        let mut steps = 0;
+        while !is_executor_finished(executor) {
+          let result = step_executor(executor);
+          assert_eq!(result.page_count, 1);
+          let pages = slice::from_raw_parts(result.pages, result.page_count);
+          let data = slice::from_raw_parts(pages[0].data, pages[0].size);
+          assert_eq!(data.len(), 4096);
+          assert_eq!(data[4095], 0xaa);
+          steps += 1;
+        }
+        assert!(steps > 1);
+
+        free_executor(executor);
+      }
+
+      // The executor copied the initial memory, the caller's buffer is
+      // untouched
+      assert_eq!(memory[0], 0xaa);
+    }
+
+    #[test]
+    fn lifecycle_without_pages() {
+      let program = create_test_program();
+      let registers = [0u64; 13];
+
+      unsafe {
+        let executor = create_executor(
+          program.as_ptr(),
+          program.len(),
+          ptr::null(),
+          0,
+          registers.as_ptr(),
+          10000,
+        );
+        assert!(!executor.is_null());
+
+        while !is_executor_finished(executor) {
+          let result = step_executor(executor);
+          assert_eq!(result.page_count, 0);
+        }
+
+        free_executor(executor);
+        free_executor(ptr::null_mut());
+      }
+    }
+
+    #[test]
+    fn lifecycle_invalid_program() {
+      let registers = [0u64; 13];
+      let garbage = [0u8; 16];
+
+      unsafe {
+        let executor = create_executor(
+          garbage.as_ptr(),
+          garbage.len(),
+          ptr::null(),
+          0,
+          registers.as_ptr(),
+          10000,
+        );
+        assert!(executor.is_null());
+      }
+    }
   }
 }
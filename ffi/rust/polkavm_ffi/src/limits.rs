@@ -0,0 +1,352 @@
+//! Interrupting runs and capping them by step count or wall-clock time
+//!
+//! Gas does not bound a run when the fuzzer picks limits close to
+//! `u64::MAX`. Limits apply to every call that runs more than a single step,
+//! a run they stop ends with `Interrupted` and can be continued. Inner
+//! machines invoked during a run count against the limits of that run.
+
+use std::{
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
+  time::{Duration, Instant},
+};
+
+use crate::{ExecutionResult, ExecutionStatus, ProgramExecutor};
+
+/// Steps between two reads of the clock
+const CLOCK_CHECK_INTERVAL: u64 = 1024;
+
+#[derive(Debug, Default)]
+pub(crate) struct RunLimits {
+  max_steps: Option<u64>,
+  max_duration: Option<Duration>,
+  interrupt: Arc<AtomicBool>,
+  /// What is left of the limits of the run in progress
+  budget: Option<RunBudget>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct RunBudget {
+  steps_left: Option<u64>,
+  deadline: Option<Instant>,
+}
+
+/// A run was stopped by its limits or an interrupt
+#[derive(Debug)]
+pub(crate) struct Interrupted;
+
+impl RunLimits {
+  /// Starts a run with the full limits
+  ///
+  /// The interrupt flag is left alone, it is only cleared once a run has
+  /// observed it, so an interrupt raised between runs stops the next one.
+  fn start_run(&mut self) {
+    self.budget = Some(RunBudget {
+      steps_left: self.max_steps,
+      deadline: self.max_duration.map(|d| Instant::now() + d),
+    });
+  }
+
+  /// Returns true if the run has to stop before its next step
+  fn is_exhausted(&self, check_clock: bool) -> bool {
+    let Some(budget) = &self.budget else {
+      return false;
+    };
+    self.interrupt.swap(false, Ordering::Relaxed)
+      || budget.steps_left == Some(0)
+      || check_clock
+        && budget
+          .deadline
+          .is_some_and(|deadline| Instant::now() >= deadline)
+  }
+
+  fn charge_step(&mut self) {
+    if let Some(RunBudget {
+      steps_left: Some(steps_left),
+      ..
+    }) = &mut self.budget
+    {
+      *steps_left -= 1;
+    }
+  }
+
+  /// Runs an inner machine on what is left of the run in progress, an
+  /// interrupt of this run stops the machine as well
+  pub(crate) fn run_child(
+    &mut self,
+    child: &mut ProgramExecutor,
+    should_stop: impl Fn(&ProgramExecutor) -> bool,
+  ) -> Result<(), Interrupted> {
+    // Host calls can also be served outside of a run through the FFI
+    let outside_run = self.budget.is_none();
+    if outside_run {
+      self.start_run();
+    }
+
+    let child_limits = std::mem::replace(
+      &mut child.limits,
+      RunLimits {
+        interrupt: self.interrupt.clone(),
+        budget: self.budget,
+        ..RunLimits::default()
+      },
+    );
+    child.run_within_budget(should_stop);
+    self.budget = child.limits.budget;
+    child.limits = child_limits;
+
+    if outside_run {
+      self.budget = None;
+    }
+    match child.current_status {
+      ExecutionStatus::Interrupted => Err(Interrupted),
+      _ => Ok(()),
+    }
+  }
+}
+
+/// Interrupts the run of an executor from another thread
+#[derive(Debug, Clone)]
+pub struct InterruptHandle(Arc<AtomicBool>);
+
+impl InterruptHandle {
+  /// Stops the current run before its next step, or the next run before its
+  /// first step if none is in progress
+  pub fn interrupt(&self) {
+    self.0.store(true, Ordering::Relaxed);
+  }
+}
+
+impl ProgramExecutor {
+  /// Caps the number of steps a single run may take
+  pub fn set_step_limit(&mut self, max_steps: Option<u64>) {
+    self.limits.max_steps = max_steps;
+  }
+
+  /// Caps the wall-clock time a single run may take
+  pub fn set_time_limit(&mut self, max_duration: Option<Duration>) {
+    self.limits.max_duration = max_duration;
+  }
+
+  pub fn interrupt_handle(&self) -> InterruptHandle {
+    InterruptHandle(self.limits.interrupt.clone())
+  }
+
+  /// Steps until `should_stop` holds, the program finishes or a limit is
+  /// reached. At least one step is taken unless a limit prevents it.
+  ///
+  /// An interrupt raised while no run is in progress stops this run before
+  /// its first step.
+  pub(crate) fn run_until_stopped(
+    &mut self,
+    should_stop: impl Fn(&ProgramExecutor) -> bool,
+  ) {
+    self.limits.start_run();
+    self.run_within_budget(should_stop);
+    self.limits.budget = None;
+  }
+
+  /// Steps like `run_until_stopped` on what is left of the limits of the
+  /// run in progress
+  pub(crate) fn run_within_budget(
+    &mut self,
+    should_stop: impl Fn(&ProgramExecutor) -> bool,
+  ) {
+    let mut steps = 0u64;
+    while !self.is_finished() {
+      if self.limits.is_exhausted(steps % CLOCK_CHECK_INTERVAL == 0) {
+        self.current_status = ExecutionStatus::Interrupted;
+        return;
+      }
+
+      self.advance();
+      steps += 1;
+      self.limits.charge_step();
+      if should_stop(self) {
+        return;
+      }
+    }
+  }
+
+  /// Serves host calls with `serve` between steps until the program
+  /// finishes or a limit is reached, all on the limits of a single run
+  ///
+  /// `serve` returns false if the executor is not stopped on a host call.
+  pub(crate) fn run_serving_host_calls(
+    &mut self,
+    mut serve: impl FnMut(&mut ProgramExecutor) -> bool,
+  ) {
+    self.limits.start_run();
+    loop {
+      // A host call cut short by the limits is made again first
+      if self.host_call.is_none() {
+        self.run_within_budget(|executor| executor.host_call.is_some());
+      }
+      if !serve(self) || self.current_status == ExecutionStatus::Interrupted {
+        break;
+      }
+    }
+    self.limits.budget = None;
+  }
+}
+
+/// Caps the steps and the wall-clock time of every run, 0 means unlimited
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts a raw pointer as input
+#[no_mangle]
+pub unsafe extern "C" fn executor_set_run_limits(
+  executor: *mut ProgramExecutor,
+  max_steps: u64,
+  max_millis: u64,
+) {
+  let executor = &mut *executor;
+  executor.set_step_limit((max_steps != 0).then_some(max_steps));
+  executor.set_time_limit(
+    (max_millis != 0).then(|| Duration::from_millis(max_millis)),
+  );
+}
+
+/// Runs until the program finishes or a limit is reached
+///
+/// # Safety
+///
+/// This function is unsafe because it:
+/// - Accepts a raw pointer as input
+/// - Returns pages borrowed from the executor, they must not be used after
+///   the next step or after the executor is freed
+#[no_mangle]
+pub unsafe extern "C" fn executor_run(
+  executor: *mut ProgramExecutor,
+) -> ExecutionResult {
+  let executor = &mut *executor;
+  executor.run_to_completion();
+  executor.create_execution_result()
+}
+
+/// Returns a handle that can interrupt the executor from any thread, it
+/// stays valid after the executor is freed
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts a raw pointer as input
+#[no_mangle]
+pub unsafe extern "C" fn executor_interrupt_handle(
+  executor: *const ProgramExecutor,
+) -> *mut InterruptHandle {
+  Box::into_raw(Box::new((&*executor).interrupt_handle()))
+}
+
+/// Stops the current run of the executor before its next step, or the next
+/// run before its first step if none is in progress
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts a raw pointer as input
+#[no_mangle]
+pub unsafe extern "C" fn interrupt_executor(handle: *const InterruptHandle) {
+  (&*handle).interrupt();
+}
+
+/// Frees an interrupt handle
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts a raw pointer as input
+#[no_mangle]
+pub unsafe extern "C" fn free_interrupt_handle(handle: *mut InterruptHandle) {
+  if !handle.is_null() {
+    drop(Box::from_raw(handle));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::thread;
+
+  use polkavm_common::{program::asm, writer::ProgramBlobBuilder};
+
+  use super::*;
+  use crate::MemoryPage;
+
+  fn endless_loop() -> ProgramExecutor {
+    let mut builder = ProgramBlobBuilder::new();
+    builder.add_export_by_basic_block(0, b"main");
+    builder.set_code(&[asm::jump(0)], &[]);
+    let program = builder.into_vec();
+    let registers = [0u64; 13];
+    let pages: [MemoryPage; 0] = [];
+
+    unsafe {
+      ProgramExecutor::new(
+        program.as_ptr(),
+        program.len(),
+        pages.as_ptr(),
+        0,
+        registers.as_ptr(),
+        u64::MAX >> 1,
+      )
+      .expect("Failed to create executor")
+    }
+  }
+
+  #[test]
+  fn test_step_limit() {
+    let mut executor = endless_loop();
+    executor.set_step_limit(Some(100));
+    executor.run_to_completion();
+    assert_eq!(executor.current_status, ExecutionStatus::Interrupted);
+
+    // The run can be continued
+    executor.run_to_completion();
+    assert_eq!(executor.current_status, ExecutionStatus::Interrupted);
+    assert!(!executor.is_finished());
+  }
+
+  #[test]
+  fn test_time_limit() {
+    let mut executor = endless_loop();
+    executor.set_time_limit(Some(Duration::from_millis(10)));
+    executor.run_to_completion();
+    assert_eq!(executor.current_status, ExecutionStatus::Interrupted);
+  }
+
+  #[test]
+  fn test_interrupt_from_other_thread() {
+    let mut executor = endless_loop();
+    // Keeps the test from hanging if the interrupt is lost
+    executor.set_time_limit(Some(Duration::from_secs(10)));
+
+    let handle = executor.interrupt_handle();
+    let start = Instant::now();
+    let interrupter = thread::spawn(move || {
+      thread::sleep(Duration::from_millis(20));
+      handle.interrupt();
+    });
+    executor.run_to_completion();
+    interrupter.join().unwrap();
+
+    assert_eq!(executor.current_status, ExecutionStatus::Interrupted);
+    assert!(start.elapsed() < Duration::from_secs(10));
+  }
+
+  #[test]
+  fn test_interrupt_between_runs_stops_next_run() {
+    let mut executor = endless_loop();
+    executor.set_step_limit(Some(100));
+
+    executor.interrupt_handle().interrupt();
+    let gas = executor.gas();
+    executor.run_to_completion();
+    assert_eq!(executor.current_status, ExecutionStatus::Interrupted);
+    assert_eq!(executor.gas(), gas);
+
+    // The interrupt is consumed by the run that observed it
+    executor.run_to_completion();
+    assert_eq!(executor.current_status, ExecutionStatus::Interrupted);
+    assert!(executor.gas() < gas);
+  }
+}
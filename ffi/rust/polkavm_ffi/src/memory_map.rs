@@ -0,0 +1,181 @@
+use std::collections::BTreeMap;
+
+use crate::ProgramExecutor;
+
+/// Page size used for dynamic paging
+pub const PAGE_SIZE: u32 = 0x1000;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) struct PageInfo {
+  pub(crate) is_writable: bool,
+  /// Mapped while resolving a page fault rather than at creation
+  pub(crate) is_dynamic: bool,
+}
+
+/// A run of contiguous pages with the same access
+#[repr(C)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct MappedRange {
+  pub address: u32,
+  pub length: u32,
+  pub is_writable: bool,
+  pub is_dynamic: bool,
+}
+
+/// Tracks which pages are mapped into an executor
+///
+/// polkavm does not expose its page tables, so every mapping done through
+/// this crate is recorded here.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct PageMap {
+  pages: BTreeMap<u32, PageInfo>,
+}
+
+impl PageMap {
+  /// Records the pages covering `[address, address + size)`
+  pub(crate) fn map(&mut self, address: u32, size: u32, info: PageInfo) {
+    let first = address / PAGE_SIZE;
+    let last = (address as u64 + size as u64).div_ceil(PAGE_SIZE as u64);
+    for page in first as u64..last {
+      self.pages.insert(page as u32 * PAGE_SIZE, info);
+    }
+  }
+
+  /// Forgets the pages covering `[address, address + size)`
+  pub(crate) fn unmap(&mut self, address: u32, size: u32) {
+    let first = address / PAGE_SIZE;
+    let last = (address as u64 + size as u64).div_ceil(PAGE_SIZE as u64);
+    for page in first as u64..last {
+      self.pages.remove(&(page as u32 * PAGE_SIZE));
+    }
+  }
+
+  /// Returns true if every page touched by the range is mapped, and
+  /// writable when `write` is set
+  pub(crate) fn is_accessible(
+    &self,
+    address: u32,
+    length: u32,
+    write: bool,
+  ) -> bool {
+    if length == 0 {
+      return true;
+    }
+
+    let end = address as u64 + length as u64;
+    if end > 1 << 32 {
+      return false;
+    }
+
+    let first = address / PAGE_SIZE;
+    let last = end.div_ceil(PAGE_SIZE as u64);
+    (first as u64..last).all(|page| {
+      self
+        .pages
+        .get(&(page as u32 * PAGE_SIZE))
+        .is_some_and(|info| !write || info.is_writable)
+    })
+  }
+
+  /// Iterates over page addresses and their access
+  pub(crate) fn pages(&self) -> impl Iterator<Item = (u32, PageInfo)> + '_ {
+    self.pages.iter().map(|(&address, &info)| (address, info))
+  }
+
+  /// Merges the mapped pages into ranges, in address order
+  pub(crate) fn ranges(&self) -> Vec<MappedRange> {
+    let mut ranges: Vec<MappedRange> = Vec::new();
+    for (address, info) in self.pages() {
+      match ranges.last_mut() {
+        Some(last)
+          if last.address as u64 + last.length as u64 == address as u64
+            && last.is_writable == info.is_writable
+            && last.is_dynamic == info.is_dynamic =>
+        {
+          last.length += PAGE_SIZE;
+        }
+        _ => ranges.push(MappedRange {
+          address,
+          length: PAGE_SIZE,
+          is_writable: info.is_writable,
+          is_dynamic: info.is_dynamic,
+        }),
+      }
+    }
+    ranges
+  }
+}
+
+impl ProgramExecutor {
+  /// Returns the current memory map, adjacent pages with the same access
+  /// are merged into one range
+  pub fn memory_map(&self) -> Vec<MappedRange> {
+    self.page_map.ranges()
+  }
+}
+
+/// Writes the executor's mapped page ranges into `ranges_out`
+///
+/// Returns the total number of ranges, at most `capacity` entries are
+/// written. Call with a capacity of 0 to query the required size.
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts raw pointers as input
+#[no_mangle]
+pub unsafe extern "C" fn executor_memory_map(
+  executor: *const ProgramExecutor,
+  ranges_out: *mut MappedRange,
+  capacity: usize,
+) -> usize {
+  let ranges = (&*executor).memory_map();
+  if !ranges_out.is_null() {
+    let count = ranges.len().min(capacity);
+    std::ptr::copy_nonoverlapping(ranges.as_ptr(), ranges_out, count);
+  }
+  ranges.len()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_ranges_merge_pages_with_same_access() {
+    let read_only = PageInfo {
+      is_writable: false,
+      is_dynamic: false,
+    };
+    let writable = PageInfo {
+      is_writable: true,
+      is_dynamic: false,
+    };
+    let dynamic = PageInfo {
+      is_writable: true,
+      is_dynamic: true,
+    };
+
+    let mut page_map = PageMap::default();
+    page_map.map(0x10000, 2 * PAGE_SIZE, read_only);
+    page_map.map(0x12000, PAGE_SIZE + 1, writable);
+    page_map.map(0x14000, PAGE_SIZE, dynamic);
+    page_map.map(0x20000, PAGE_SIZE, writable);
+    page_map.unmap(0x13000, PAGE_SIZE);
+
+    let range = |address, length, info: PageInfo| MappedRange {
+      address,
+      length,
+      is_writable: info.is_writable,
+      is_dynamic: info.is_dynamic,
+    };
+    assert_eq!(
+      page_map.ranges(),
+      vec![
+        range(0x10000, 2 * PAGE_SIZE, read_only),
+        range(0x12000, PAGE_SIZE, writable),
+        range(0x14000, PAGE_SIZE, dynamic),
+        range(0x20000, PAGE_SIZE, writable),
+      ]
+    );
+  }
+}
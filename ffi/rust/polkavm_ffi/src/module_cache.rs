@@ -0,0 +1,158 @@
+//! Shared engine and module cache
+//!
+//! Parsing a blob and building its `Module` dominates executor creation. The
+//! same service code is instantiated over and over, so modules are cached by
+//! the content hash of their blob and only a new `RawInstance` is created per
+//! executor.
+
+use std::{
+  collections::{hash_map::DefaultHasher, HashMap},
+  hash::{Hash, Hasher},
+  sync::{Arc, Mutex, OnceLock},
+};
+
+use polkavm::{
+  BackendKind, Config, Engine, GasMeteringKind, Module, ModuleConfig,
+  ProgramBlob,
+};
+
+use crate::{instructions::InstructionIndex, InitializationError};
+
+/// The polkavm backend an executor runs on
+#[repr(C)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Backend {
+  Interpreter = 0,
+  Compiler = 1,
+}
+
+/// Upper bound on cached modules, the cache is flushed when it is reached
+const MAX_CACHED_MODULES: usize = 256;
+
+#[derive(Clone)]
+pub(crate) struct CachedModule {
+  pub(crate) module: Module,
+  pub(crate) instructions: Arc<InstructionIndex>,
+}
+
+struct CacheEntry {
+  bytes: Vec<u8>,
+  cached: CachedModule,
+}
+
+#[derive(Default)]
+struct ModuleCache {
+  entries: HashMap<(Backend, u64), Vec<CacheEntry>>,
+  len: usize,
+}
+
+fn engine(backend: Backend) -> Result<&'static Engine, InitializationError> {
+  static INTERPRETER: OnceLock<Option<Engine>> = OnceLock::new();
+  static COMPILER: OnceLock<Option<Engine>> = OnceLock::new();
+
+  let (engine, kind) = match backend {
+    Backend::Interpreter => (&INTERPRETER, BackendKind::Interpreter),
+    Backend::Compiler => (&COMPILER, BackendKind::Compiler),
+  };
+
+  engine
+    .get_or_init(|| {
+      let mut config = Config::new();
+      config.set_backend(Some(kind));
+      config.set_allow_dynamic_paging(true);
+      Engine::new(&config).ok()
+    })
+    .as_ref()
+    .ok_or(InitializationError::EngineError)
+}
+
+/// Returns true if the backend can be used on this host
+pub fn is_backend_available(backend: Backend) -> bool {
+  engine(backend).is_ok()
+}
+
+fn cache() -> &'static Mutex<ModuleCache> {
+  static CACHE: OnceLock<Mutex<ModuleCache>> = OnceLock::new();
+  CACHE.get_or_init(Default::default)
+}
+
+fn compile(
+  raw_bytes: &[u8],
+  backend: Backend,
+) -> Result<CachedModule, InitializationError> {
+  // Parse program blob
+  let blob = ProgramBlob::parse(raw_bytes.to_vec().into())
+    .map_err(|_| InitializationError::ProgramError)?;
+
+  // Configure and create module
+  let mut module_config = ModuleConfig::default();
+  module_config.set_strict(true);
+  module_config.set_gas_metering(Some(GasMeteringKind::Sync));
+  module_config.set_dynamic_paging(true);
+  module_config.set_step_tracing(true);
+
+  let instructions = Arc::new(InstructionIndex::new(&blob));
+  let module = Module::from_blob(engine(backend)?, &module_config, blob)
+    .map_err(|_| InitializationError::ModuleError)?;
+
+  Ok(CachedModule {
+    module,
+    instructions,
+  })
+}
+
+/// Returns the module for a blob, compiling it on first use
+pub(crate) fn module_for(
+  raw_bytes: &[u8],
+  backend: Backend,
+) -> Result<CachedModule, InitializationError> {
+  let mut hasher = DefaultHasher::new();
+  raw_bytes.hash(&mut hasher);
+  let key = (backend, hasher.finish());
+
+  {
+    let cache = cache().lock().unwrap_or_else(|err| err.into_inner());
+    // Compare the full blob, the hash only narrows down the candidates
+    if let Some(entry) = cache
+      .entries
+      .get(&key)
+      .and_then(|bucket| bucket.iter().find(|e| e.bytes == raw_bytes))
+    {
+      return Ok(entry.cached.clone());
+    }
+  }
+
+  // Compile outside the lock so other threads are not blocked
+  let cached = compile(raw_bytes, backend)?;
+
+  let mut cache = cache().lock().unwrap_or_else(|err| err.into_inner());
+  if cache.len >= MAX_CACHED_MODULES {
+    cache.entries.clear();
+    cache.len = 0;
+  }
+  cache.entries.entry(key).or_default().push(CacheEntry {
+    bytes: raw_bytes.to_vec(),
+    cached: cached.clone(),
+  });
+  cache.len += 1;
+
+  Ok(cached)
+}
+
+/// Drops all cached modules
+pub fn clear() {
+  let mut cache = cache().lock().unwrap_or_else(|err| err.into_inner());
+  cache.entries.clear();
+  cache.len = 0;
+}
+
+/// Returns the number of cached modules
+pub fn len() -> usize {
+  cache().lock().unwrap_or_else(|err| err.into_inner()).len
+}
+
+/// Drops all cached modules, executors created earlier keep working
+#[no_mangle]
+pub extern "C" fn clear_module_cache() {
+  clear();
+}
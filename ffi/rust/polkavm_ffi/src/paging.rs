@@ -0,0 +1,191 @@
+use std::slice;
+
+use crate::{memory_map::PageInfo, ExecutionStatus, ProgramExecutor};
+
+/// What to do with a page fault raised under dynamic paging
+#[repr(C)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PageFaultAction {
+  /// Treat the fault as final, execution stays in the `Segfault` state
+  Final = 0,
+  /// Map a zero-filled page at the faulting address and resume
+  MapZeroPage = 1,
+  /// Map a page filled with caller provided data and resume
+  MapPage = 2,
+}
+
+/// Access rights of a page mapped while resolving a fault
+#[repr(C)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PageAccess {
+  ReadOnly = 0,
+  ReadWrite = 1,
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PageFaultError {
+  NoPendingFault = 1,
+  DataTooLarge = 2,
+  MemoryError = 3,
+}
+
+/// A fault reported by polkavm which has not been resolved yet
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PendingPageFault {
+  pub(crate) page_address: u32,
+  pub(crate) page_size: u32,
+}
+
+impl ProgramExecutor {
+  /// Resolves the pending page fault
+  ///
+  /// Mapping a page moves the executor back into the `Running` state, the
+  /// next step re-executes the faulting instruction.
+  pub fn resolve_page_fault(
+    &mut self,
+    action: PageFaultAction,
+    access: PageAccess,
+    data: &[u8],
+  ) -> Result<(), PageFaultError> {
+    // The fault stays pending until the page is mapped, so the caller can
+    // retry after an error
+    let fault = self
+      .pending_page_fault
+      .ok_or(PageFaultError::NoPendingFault)?;
+
+    if action == PageFaultAction::Final {
+      self.pending_page_fault = None;
+      return Ok(());
+    }
+
+    if data.len() > fault.page_size as usize {
+      return Err(PageFaultError::DataTooLarge);
+    }
+
+    self
+      .instance
+      .zero_memory(fault.page_address, fault.page_size)
+      .map_err(|_| PageFaultError::MemoryError)?;
+
+    if action == PageFaultAction::MapPage && !data.is_empty() {
+      self
+        .instance
+        .write_memory(fault.page_address, data)
+        .map_err(|_| PageFaultError::MemoryError)?;
+    }
+
+    if access == PageAccess::ReadOnly {
+      self
+        .instance
+        .protect_memory(fault.page_address, fault.page_size)
+        .map_err(|_| PageFaultError::MemoryError)?;
+    }
+
+    self.page_map.map(
+      fault.page_address,
+      fault.page_size,
+      PageInfo {
+        is_writable: access == PageAccess::ReadWrite,
+        is_dynamic: true,
+      },
+    );
+
+    self.pending_page_fault = None;
+    self.current_status = ExecutionStatus::Running;
+    self.segfault_address = 0;
+
+    Ok(())
+  }
+}
+
+/// Resolves the page fault the executor stopped on
+///
+/// `data` is only used with `PageFaultAction::MapPage` and may be shorter
+/// than a page, the remainder of the page is zero filled.
+/// Returns 0 on success or a `PageFaultError` code
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts raw pointers as input
+#[no_mangle]
+pub unsafe extern "C" fn resolve_page_fault(
+  executor: *mut ProgramExecutor,
+  action: PageFaultAction,
+  access: PageAccess,
+  data: *const u8,
+  data_len: usize,
+) -> i32 {
+  let data = if data.is_null() {
+    &[][..]
+  } else {
+    slice::from_raw_parts(data, data_len)
+  };
+
+  match (&mut *executor).resolve_page_fault(action, access, data) {
+    Ok(()) => 0,
+    Err(err) => err as i32,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use polkavm::Reg;
+  use polkavm_common::{program::asm, writer::ProgramBlobBuilder};
+
+  use super::*;
+  use crate::MemoryPage;
+
+  #[test]
+  fn test_resolve_page_fault_with_zero_page() {
+    let mut builder = ProgramBlobBuilder::new();
+    builder.add_export_by_basic_block(0, b"main");
+    builder.set_code(
+      &[
+        asm::store_imm_u32(0x30000, 0x12345678),
+        asm::load_imm(Reg::T0, 0xdeadbeef),
+        asm::ret(),
+      ],
+      &[],
+    );
+    let program = builder.into_vec();
+    let registers = [0u64; 13];
+    let pages: [MemoryPage; 0] = [];
+
+    unsafe {
+      let mut executor = ProgramExecutor::new(
+        program.as_ptr(),
+        program.len(),
+        pages.as_ptr(),
+        0,
+        registers.as_ptr(),
+        10000,
+      )
+      .expect("Failed to create executor");
+
+      executor.run_to_completion();
+      assert_eq!(executor.current_status, ExecutionStatus::Segfault);
+      assert_eq!(executor.segfault_address, 0x30000);
+
+      executor
+        .resolve_page_fault(
+          PageFaultAction::MapZeroPage,
+          PageAccess::ReadWrite,
+          &[],
+        )
+        .expect("Failed to resolve page fault");
+
+      executor.run_to_completion();
+      assert_eq!(executor.current_status, ExecutionStatus::Trap);
+      assert_eq!(executor.instance.read_u32(0x30000).unwrap(), 0x12345678);
+      assert_eq!(
+        executor.resolve_page_fault(
+          PageFaultAction::Final,
+          PageAccess::ReadOnly,
+          &[]
+        ),
+        Err(PageFaultError::NoPendingFault)
+      );
+    }
+  }
+}
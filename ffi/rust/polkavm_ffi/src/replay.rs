@@ -0,0 +1,611 @@
+//! Record and replay of host calls
+//!
+//! While recording, every host call the executor stops on is logged with
+//! the memory the host wrote and the registers and gas it left behind, no
+//! matter whether the host is `host_calls` or the caller going through the
+//! state accessors. Replaying the log runs the program again without any
+//! host, which isolates the PVM from the host side of a divergence. Logs can
+//! also be built from a run of our own PVM.
+
+use std::{ptr, slice};
+
+use polkavm::Reg;
+
+use crate::{
+  host_calls::HostCallOutcome, ExecutionResult, ExecutionStatus,
+  ProgramExecutor,
+};
+
+const LOG_MAGIC: &[u8; 4] = b"HCL\x01";
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct MemoryWrite {
+  pub address: u32,
+  pub data: Vec<u8>,
+}
+
+/// Effects of a single host call
+#[derive(Debug, PartialEq, Clone)]
+pub struct HostCallRecord {
+  pub index: u32,
+  pub outcome: HostCallOutcome,
+  /// Gas and registers after the host call
+  pub gas: i64,
+  pub registers: [u64; 13],
+  /// Bit i is set if the host changed register i, the others are expected
+  /// to hold the same value before the host call
+  pub written_registers: u16,
+  pub writes: Vec<MemoryWrite>,
+}
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct HostCallLog {
+  pub records: Vec<HostCallRecord>,
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ReplayError {
+  /// The program made more host calls than were recorded
+  LogExhausted = 1,
+  /// The program made a different host call than was recorded
+  HostCallMismatch = 2,
+  /// A recorded write targets memory that is not writable
+  MemoryError = 3,
+  /// The program finished with recorded host calls left over
+  LogNotExhausted = 4,
+  /// A register the host did not write differs from the record, the PVM
+  /// diverged before the host call
+  RegisterMismatch = 5,
+}
+
+/// Outcome of a recorded host call from its byte, host calls are only
+/// recorded once they finish so `Interrupted` is rejected
+fn recorded_outcome(byte: u8) -> Option<HostCallOutcome> {
+  match byte {
+    0 => Some(HostCallOutcome::Continue),
+    1 => Some(HostCallOutcome::Panic),
+    2 => Some(HostCallOutcome::OutOfGas),
+    _ => None,
+  }
+}
+
+impl HostCallLog {
+  /// Encodes the log as `magic ‖ E_4(count) ‖ records`, every record being
+  /// `E_4(index) ‖ outcome ‖ E_8(gas) ‖ E_8(registers) ‖
+  /// E_2(written registers) ‖ E_4(count) ‖ (E_4(address) ‖ E_4(len) ‖ data)*`
+  pub fn encode(&self) -> Vec<u8> {
+    let mut out = LOG_MAGIC.to_vec();
+    out.extend_from_slice(&(self.records.len() as u32).to_le_bytes());
+    for record in &self.records {
+      out.extend_from_slice(&record.index.to_le_bytes());
+      out.push(record.outcome as u8);
+      out.extend_from_slice(&record.gas.to_le_bytes());
+      for value in record.registers {
+        out.extend_from_slice(&value.to_le_bytes());
+      }
+      out.extend_from_slice(&record.written_registers.to_le_bytes());
+      out.extend_from_slice(&(record.writes.len() as u32).to_le_bytes());
+      for write in &record.writes {
+        out.extend_from_slice(&write.address.to_le_bytes());
+        out.extend_from_slice(&(write.data.len() as u32).to_le_bytes());
+        out.extend_from_slice(&write.data);
+      }
+    }
+    out
+  }
+
+  pub fn decode(bytes: &[u8]) -> Option<Self> {
+    let mut reader = Reader(bytes);
+    if reader.take(4)? != LOG_MAGIC {
+      return None;
+    }
+
+    let count = reader.u32()?;
+    let mut records = Vec::new();
+    for _ in 0..count {
+      let index = reader.u32()?;
+      let outcome = recorded_outcome(reader.take(1)?[0])?;
+      let gas = reader.u64()? as i64;
+      let mut registers = [0; 13];
+      for value in &mut registers {
+        *value = reader.u64()?;
+      }
+      let written_registers = reader.u16()?;
+      let write_count = reader.u32()?;
+      let mut writes = Vec::new();
+      for _ in 0..write_count {
+        let address = reader.u32()?;
+        let len = reader.u32()? as usize;
+        writes.push(MemoryWrite {
+          address,
+          data: reader.take(len)?.to_vec(),
+        });
+      }
+      records.push(HostCallRecord {
+        index,
+        outcome,
+        gas,
+        registers,
+        written_registers,
+        writes,
+      });
+    }
+
+    reader.0.is_empty().then_some(Self { records })
+  }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+  fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+    if self.0.len() < len {
+      return None;
+    }
+    let (head, tail) = self.0.split_at(len);
+    self.0 = tail;
+    Some(head)
+  }
+
+  fn u16(&mut self) -> Option<u16> {
+    Some(u16::from_le_bytes(self.take(2)?.try_into().ok()?))
+  }
+
+  fn u32(&mut self) -> Option<u32> {
+    Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+  }
+
+  fn u64(&mut self) -> Option<u64> {
+    Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+  }
+}
+
+/// Host call the executor is stopped on while recording
+#[derive(Debug)]
+struct PendingHostCall {
+  index: u32,
+  /// Registers before the host call
+  registers: [u64; 13],
+  writes: Vec<MemoryWrite>,
+}
+
+/// Log being recorded and the host call the executor is stopped on
+#[derive(Debug, Default)]
+pub(crate) struct HostCallRecorder {
+  log: HostCallLog,
+  pending: Option<PendingHostCall>,
+}
+
+impl ProgramExecutor {
+  /// Starts recording host calls, discarding any previous recording
+  pub fn start_recording(&mut self) {
+    self.recorder = Some(HostCallRecorder::default());
+  }
+
+  /// Stops recording and returns the log
+  pub fn take_recording(&mut self) -> Option<HostCallLog> {
+    self.finish_host_call_record();
+    self.recorder.take().map(|recorder| recorder.log)
+  }
+
+  /// Called when the executor stops on host call `index`
+  pub(crate) fn begin_host_call_record(&mut self, index: u32) {
+    if let Some(recorder) = &mut self.recorder {
+      recorder.pending = Some(PendingHostCall {
+        index,
+        registers: Reg::ALL.map(|reg| self.instance.reg(reg)),
+        writes: Vec::new(),
+      });
+    }
+  }
+
+  /// Called for every write through the state accessors
+  pub(crate) fn record_memory_write(&mut self, address: u32, data: &[u8]) {
+    if let Some(pending) = self
+      .recorder
+      .as_mut()
+      .and_then(|recorder| recorder.pending.as_mut())
+    {
+      pending.writes.push(MemoryWrite {
+        address,
+        data: data.to_vec(),
+      });
+    }
+  }
+
+  /// Completes the record of the pending host call, called when execution
+  /// resumes or the recording is taken
+  pub(crate) fn finish_host_call_record(&mut self) {
+    let Some(pending) = self
+      .recorder
+      .as_mut()
+      .and_then(|recorder| recorder.pending.take())
+    else {
+      return;
+    };
+
+    let outcome = match self.current_status {
+      ExecutionStatus::Trap => HostCallOutcome::Panic,
+      ExecutionStatus::OutOfGas => HostCallOutcome::OutOfGas,
+      _ => HostCallOutcome::Continue,
+    };
+    let registers = Reg::ALL.map(|reg| self.instance.reg(reg));
+    let written_registers = (0..13)
+      .filter(|&i| registers[i] != pending.registers[i])
+      .fold(0u16, |mask, i| mask | 1 << i);
+    let record = HostCallRecord {
+      index: pending.index,
+      outcome,
+      gas: self.gas(),
+      registers,
+      written_registers,
+      writes: pending.writes,
+    };
+    if let Some(recorder) = &mut self.recorder {
+      recorder.log.records.push(record);
+    }
+  }
+
+  /// Runs until the program finishes or a run limit is reached, answering
+  /// host calls from `log`
+  ///
+  /// `position` is the index of the next record to answer with and is
+  /// advanced past every record used. A run stopped by the limits can be
+  /// continued by calling again with the same position, only finished
+  /// programs have to use all of the log. All host calls are served on the
+  /// limits of a single run.
+  pub fn run_with_replay(
+    &mut self,
+    log: &HostCallLog,
+    position: &mut usize,
+  ) -> Result<(), ReplayError> {
+    let mut result = Ok(());
+    self.run_serving_host_calls(|executor| {
+      if executor.host_call.is_none() {
+        return false;
+      }
+      match executor.replay_host_call(log.records.get(*position)) {
+        Ok(()) => {
+          *position += 1;
+          true
+        }
+        Err(err) => {
+          result = Err(err);
+          false
+        }
+      }
+    });
+    result?;
+
+    if self.is_finished() && *position < log.records.len() {
+      return Err(ReplayError::LogNotExhausted);
+    }
+    Ok(())
+  }
+
+  /// Answers the host call the executor is stopped on with `record`, the
+  /// host call stays pending on error
+  fn replay_host_call(
+    &mut self,
+    record: Option<&HostCallRecord>,
+  ) -> Result<(), ReplayError> {
+    let record = record.ok_or(ReplayError::LogExhausted)?;
+    if self.host_call != Some(record.index) {
+      return Err(ReplayError::HostCallMismatch);
+    }
+    let status = match record.outcome {
+      HostCallOutcome::Continue => ExecutionStatus::Running,
+      HostCallOutcome::Panic => ExecutionStatus::Trap,
+      HostCallOutcome::OutOfGas => ExecutionStatus::OutOfGas,
+      // Host calls are only recorded once they finish
+      HostCallOutcome::Interrupted => {
+        return Err(ReplayError::HostCallMismatch)
+      }
+    };
+    let written = |i: usize| record.written_registers & 1 << i != 0;
+    if Reg::ALL.into_iter().enumerate().any(|(i, reg)| {
+      !written(i) && self.instance.reg(reg) != record.registers[i]
+    }) {
+      return Err(ReplayError::RegisterMismatch);
+    }
+    for write in &record.writes {
+      self
+        .write_memory(write.address, &write.data)
+        .map_err(|_| ReplayError::MemoryError)?;
+    }
+    for (i, reg) in Reg::ALL.into_iter().enumerate() {
+      if written(i) {
+        self.instance.set_reg(reg, record.registers[i]);
+      }
+    }
+    self.set_gas(record.gas);
+
+    self.host_call = None;
+    self.current_status = status;
+    Ok(())
+  }
+}
+
+/// Starts recording the host calls of an executor
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts a raw pointer as input
+#[no_mangle]
+pub unsafe extern "C" fn executor_start_recording(
+  executor: *mut ProgramExecutor,
+) {
+  (&mut *executor).start_recording();
+}
+
+/// Stops recording and returns the log, null if the executor was not
+/// recording. Free it with `free_host_call_log`.
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts a raw pointer as input
+#[no_mangle]
+pub unsafe extern "C" fn executor_take_recording(
+  executor: *mut ProgramExecutor,
+) -> *mut HostCallLog {
+  match (&mut *executor).take_recording() {
+    Some(log) => Box::into_raw(Box::new(log)),
+    None => ptr::null_mut(),
+  }
+}
+
+/// Replays `log` against the executor, the state after the run is written
+/// to `result_out`
+///
+/// `position` is the index of the next record, start at 0 and pass it again
+/// to continue a run stopped by the limits. It is advanced past every record
+/// used.
+/// Returns 0 on success or a `ReplayError` code
+///
+/// # Safety
+///
+/// This function is unsafe because it:
+/// - Accepts raw pointers as input
+/// - Returns pages borrowed from the executor, they must not be used after
+///   the next step or after the executor is freed
+#[no_mangle]
+pub unsafe extern "C" fn executor_run_replay(
+  executor: *mut ProgramExecutor,
+  log: *const HostCallLog,
+  position: *mut usize,
+  result_out: *mut ExecutionResult,
+) -> i32 {
+  let executor = &mut *executor;
+  let status = match executor.run_with_replay(&*log, &mut *position) {
+    Ok(()) => 0,
+    Err(e) => e as i32,
+  };
+  result_out.write(executor.create_execution_result());
+  status
+}
+
+/// Creates an empty log to be filled from a run of another PVM
+#[no_mangle]
+pub extern "C" fn create_host_call_log() -> *mut HostCallLog {
+  Box::into_raw(Box::default())
+}
+
+/// Appends a host call with the gas and registers it left behind
+///
+/// `outcome` is a `HostCallOutcome` other than `Interrupted`. Bit i of
+/// `written_registers` is set if the host changed register i.
+/// Returns false, without appending, if the outcome is invalid
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts raw pointers as input
+#[no_mangle]
+pub unsafe extern "C" fn host_call_log_push(
+  log: *mut HostCallLog,
+  index: u32,
+  outcome: u8,
+  gas: i64,
+  registers: *const [u64; 13],
+  written_registers: u16,
+) -> bool {
+  let Some(outcome) = recorded_outcome(outcome) else {
+    return false;
+  };
+  (&mut *log).records.push(HostCallRecord {
+    index,
+    outcome,
+    gas,
+    registers: *registers,
+    written_registers,
+    writes: Vec::new(),
+  });
+  true
+}
+
+/// Adds a memory write to the last host call, returns false if the log is
+/// empty
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts raw pointers as input
+#[no_mangle]
+pub unsafe extern "C" fn host_call_log_push_write(
+  log: *mut HostCallLog,
+  address: u32,
+  data: *const u8,
+  len: usize,
+) -> bool {
+  let Some(record) = (&mut *log).records.last_mut() else {
+    return false;
+  };
+  record.writes.push(MemoryWrite {
+    address,
+    data: slice::from_raw_parts(data, len).to_vec(),
+  });
+  true
+}
+
+/// Encodes the log into `out`
+///
+/// Returns the full encoded length, at most `capacity` bytes are written.
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts raw pointers as input
+#[no_mangle]
+pub unsafe extern "C" fn host_call_log_encode(
+  log: *const HostCallLog,
+  out: *mut u8,
+  capacity: usize,
+) -> usize {
+  let encoded = (&*log).encode();
+  if !out.is_null() {
+    let count = encoded.len().min(capacity);
+    ptr::copy_nonoverlapping(encoded.as_ptr(), out, count);
+  }
+  encoded.len()
+}
+
+/// Decodes a log, returns null if the bytes are not a valid log
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts raw pointers as input
+#[no_mangle]
+pub unsafe extern "C" fn host_call_log_decode(
+  bytes: *const u8,
+  len: usize,
+) -> *mut HostCallLog {
+  match HostCallLog::decode(slice::from_raw_parts(bytes, len)) {
+    Some(log) => Box::into_raw(Box::new(log)),
+    None => ptr::null_mut(),
+  }
+}
+
+/// Frees a host call log
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts a raw pointer as input
+#[no_mangle]
+pub unsafe extern "C" fn free_host_call_log(log: *mut HostCallLog) {
+  if !log.is_null() {
+    drop(Box::from_raw(log));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use polkavm_common::{program::asm, writer::ProgramBlobBuilder};
+
+  use super::*;
+  use crate::{
+    host_calls::{host_call_id, HostEnvironment, ServiceAccount},
+    MemoryPage,
+  };
+
+  const DATA: u32 = 0x20000;
+
+  fn executor() -> ProgramExecutor {
+    // Reads the service info into DATA and stores the balance in T0
+    let mut builder = ProgramBlobBuilder::new_64bit();
+    builder.add_export_by_basic_block(0, b"main");
+    builder.set_code(
+      &[
+        asm::load_imm64(Reg::A0, u64::MAX),
+        asm::load_imm(Reg::A1, DATA),
+        asm::load_imm(Reg::A2, 0),
+        asm::load_imm(Reg::A3, 96),
+        asm::ecalli(host_call_id::INFO),
+        asm::load_u64(Reg::T0, DATA + 32),
+        asm::ret(),
+      ],
+      &[],
+    );
+    let program = builder.into_vec();
+
+    let mut memory = vec![0u8; 4096];
+    let page = MemoryPage {
+      address: DATA,
+      data: memory.as_mut_ptr(),
+      size: 4096,
+      is_writable: true,
+    };
+    let registers = [0u64; 13];
+
+    unsafe {
+      ProgramExecutor::new(
+        program.as_ptr(),
+        program.len(),
+        &page,
+        1,
+        registers.as_ptr(),
+        10000,
+      )
+      .expect("Failed to create executor")
+    }
+  }
+
+  #[test]
+  fn test_record_and_replay() {
+    let mut env = HostEnvironment::new(0);
+    let mut account = ServiceAccount::default();
+    account.info.balance = 1234;
+    env.accounts.insert(0, account);
+
+    let mut recorded = executor();
+    recorded.start_recording();
+    recorded.run_with_host(&mut env);
+    let log = recorded.take_recording().unwrap();
+
+    assert_eq!(log.records.len(), 1);
+    assert_eq!(log.records[0].index, host_call_id::INFO);
+    assert_eq!(log.records[0].writes[0].address, DATA);
+    assert_eq!(log.records[0].written_registers, 1 << Reg::A0 as u16);
+    assert_eq!(HostCallLog::decode(&log.encode()), Some(log.clone()));
+
+    let mut replayed = executor();
+    let mut position = 0;
+    assert_eq!(replayed.run_with_replay(&log, &mut position), Ok(()));
+    assert_eq!(position, 1);
+    assert_eq!(replayed.current_status, recorded.current_status);
+    assert_eq!(replayed.register(Reg::T0 as u32), Ok(1234));
+    assert_eq!(replayed.gas(), recorded.gas());
+
+    // Runs stopped by the limits continue from the same position
+    let mut resumed = executor();
+    resumed.set_step_limit(Some(2));
+    let mut position = 0;
+    for _ in 0..10 {
+      assert_eq!(resumed.run_with_replay(&log, &mut position), Ok(()));
+      if resumed.is_finished() {
+        break;
+      }
+      assert_eq!(resumed.current_status, ExecutionStatus::Interrupted);
+    }
+    assert_eq!(position, 1);
+    assert_eq!(resumed.current_status, recorded.current_status);
+    assert_eq!(resumed.register(Reg::T0 as u32), Ok(1234));
+
+    let mut exhausted = executor();
+    assert_eq!(
+      exhausted.run_with_replay(&HostCallLog::default(), &mut 0),
+      Err(ReplayError::LogExhausted)
+    );
+
+    let mut extra = log.clone();
+    extra.records.push(log.records[0].clone());
+    assert_eq!(
+      executor().run_with_replay(&extra, &mut 0),
+      Err(ReplayError::LogNotExhausted)
+    );
+
+    let mut diverged = executor();
+    diverged.set_register(Reg::T1 as u32, 1).unwrap();
+    assert_eq!(
+      diverged.run_with_replay(&log, &mut 0),
+      Err(ReplayError::RegisterMismatch)
+    );
+  }
+}
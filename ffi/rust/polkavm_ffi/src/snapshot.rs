@@ -0,0 +1,321 @@
+use std::ptr;
+
+use polkavm::{ProgramCounter, Reg};
+
+use crate::{
+  memory_map::{PageMap, PAGE_SIZE},
+  paging::PendingPageFault,
+  ExecutionStatus, ProgramExecutor,
+};
+
+/// Full copy of an executor's state which can be restored later
+pub struct ExecutorSnapshot {
+  registers: [u64; 13],
+  next_pc: Option<ProgramCounter>,
+  step_pc: Option<u32>,
+  started: bool,
+  gas: i64,
+  status: ExecutionStatus,
+  segfault_address: u32,
+  pending_page_fault: Option<PendingPageFault>,
+  host_call: Option<u32>,
+  page_map: PageMap,
+  memory: Vec<(u32, Vec<u8>)>,
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SnapshotError {
+  MemoryError = 1,
+  /// Inner machines are not captured, snapshots are refused while any exist
+  MachinesExist = 2,
+  /// Recorded host calls cannot be rolled back, restores are refused while
+  /// recording
+  Recording = 3,
+}
+
+impl ProgramExecutor {
+  /// Captures registers, PC, gas and all mapped memory
+  pub fn snapshot(&self) -> Result<ExecutorSnapshot, SnapshotError> {
+    if !self.machines.is_empty() {
+      return Err(SnapshotError::MachinesExist);
+    }
+
+    let mut registers = [0u64; 13];
+    for (i, value) in registers.iter_mut().enumerate() {
+      if let Some(reg) = Reg::from_raw(i as u32) {
+        *value = self.instance.reg(reg);
+      }
+    }
+
+    let memory = self
+      .page_map
+      .pages()
+      .map(|(address, _)| {
+        self
+          .instance
+          .read_memory(address, PAGE_SIZE)
+          .map(|data| (address, data))
+          .map_err(|_| SnapshotError::MemoryError)
+      })
+      .collect::<Result<_, _>>()?;
+
+    Ok(ExecutorSnapshot {
+      registers,
+      next_pc: if self.pc_unset {
+        None
+      } else {
+        self.instance.next_program_counter()
+      },
+      step_pc: self.step_pc,
+      started: self.started,
+      gas: self.instance.gas(),
+      status: self.current_status,
+      segfault_address: self.segfault_address,
+      pending_page_fault: self.pending_page_fault,
+      host_call: self.host_call,
+      page_map: self.page_map.clone(),
+      memory,
+    })
+  }
+
+  /// Rolls the executor back to a previously captured snapshot
+  ///
+  /// Pages mapped and inner machines created after the snapshot was taken
+  /// are dropped again.
+  pub fn restore(
+    &mut self,
+    snapshot: &ExecutorSnapshot,
+  ) -> Result<(), SnapshotError> {
+    if self.recorder.is_some() {
+      return Err(SnapshotError::Recording);
+    }
+
+    self.instance.reset_memory();
+
+    for ((address, data), (_, info)) in
+      snapshot.memory.iter().zip(snapshot.page_map.pages())
+    {
+      self
+        .instance
+        .write_memory(*address, data)
+        .map_err(|_| SnapshotError::MemoryError)?;
+
+      if !info.is_writable {
+        self
+          .instance
+          .protect_memory(*address, PAGE_SIZE)
+          .map_err(|_| SnapshotError::MemoryError)?;
+      }
+    }
+
+    for (i, &value) in snapshot.registers.iter().enumerate() {
+      if let Some(reg) = Reg::from_raw(i as u32) {
+        self.instance.set_reg(reg, value);
+      }
+    }
+
+    // The instance keeps its PC when there is none to restore, the executor
+    // tracks that it is unset instead
+    self.pc_unset = snapshot.next_pc.is_none();
+    if let Some(pc) = snapshot.next_pc {
+      self.instance.set_next_program_counter(pc);
+    }
+    self.instance.set_gas(snapshot.gas);
+
+    self.step_pc = snapshot.step_pc;
+    self.started = snapshot.started;
+    self.current_status = snapshot.status;
+    self.segfault_address = snapshot.segfault_address;
+    self.pending_page_fault = snapshot.pending_page_fault;
+    self.host_call = snapshot.host_call;
+    self.page_map = snapshot.page_map.clone();
+    self.machines.clear();
+
+    Ok(())
+  }
+}
+
+/// Captures the full state of an executor
+///
+/// Returns null on failure, including while the executor has inner
+/// machines. The snapshot must be freed with
+/// `free_executor_snapshot`.
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts a raw pointer as input
+#[no_mangle]
+pub unsafe extern "C" fn snapshot_executor(
+  executor: *const ProgramExecutor,
+) -> *mut ExecutorSnapshot {
+  match (&*executor).snapshot() {
+    Ok(snapshot) => Box::into_raw(Box::new(snapshot)),
+    Err(_) => ptr::null_mut(),
+  }
+}
+
+/// Restores an executor to a snapshot, the snapshot stays valid and can be
+/// restored again
+///
+/// Returns true on success, false on failure, including while the executor
+/// is recording host calls
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts raw pointers as input
+#[no_mangle]
+pub unsafe extern "C" fn restore_executor(
+  executor: *mut ProgramExecutor,
+  snapshot: *const ExecutorSnapshot,
+) -> bool {
+  (&mut *executor).restore(&*snapshot).is_ok()
+}
+
+/// Frees a snapshot
+///
+/// # Safety
+///
+/// This function is unsafe because it:
+/// - Deallocates memory based on raw pointers
+/// - Must be called exactly once for each created snapshot
+#[no_mangle]
+pub unsafe extern "C" fn free_executor_snapshot(
+  snapshot: *mut ExecutorSnapshot,
+) {
+  if !snapshot.is_null() {
+    drop(Box::from_raw(snapshot));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use polkavm_common::{program::asm, writer::ProgramBlobBuilder};
+
+  use super::*;
+  use crate::MemoryPage;
+
+  #[test]
+  fn test_snapshot_and_restore() {
+    let mut builder = ProgramBlobBuilder::new();
+    builder.add_export_by_basic_block(0, b"main");
+    builder.set_code(
+      &[
+        asm::store_imm_u32(0x20000, 0x12345678),
+        asm::load_imm(Reg::T0, 0xdeadbeef),
+        asm::ret(),
+      ],
+      &[],
+    );
+    let program = builder.into_vec();
+    let mut memory = vec![0u8; 4096];
+    let page = MemoryPage {
+      address: 0x20000,
+      data: memory.as_mut_ptr(),
+      size: 4096,
+      is_writable: true,
+    };
+    let registers = [0u64; 13];
+
+    unsafe {
+      let mut executor = ProgramExecutor::new(
+        program.as_ptr(),
+        program.len(),
+        &page,
+        1,
+        registers.as_ptr(),
+        10000,
+      )
+      .expect("Failed to create executor");
+
+      let snapshot = executor.snapshot().unwrap();
+      executor.run_to_completion();
+      assert_eq!(executor.instance.read_u32(0x20000).unwrap(), 0x12345678);
+      assert_eq!(executor.instance.reg(Reg::T0), 0xdeadbeef);
+
+      executor.restore(&snapshot).unwrap();
+      assert!(!executor.is_finished());
+      assert_eq!(executor.instance.read_u32(0x20000).unwrap(), 0);
+      assert_eq!(executor.instance.reg(Reg::T0), 0);
+      assert_eq!(executor.instance.gas(), 10000);
+
+      executor.run_to_completion();
+      assert_eq!(executor.current_status, ExecutionStatus::Trap);
+      assert_eq!(executor.instance.read_u32(0x20000).unwrap(), 0x12345678);
+    }
+  }
+  fn executor_for(program: &[u8]) -> ProgramExecutor {
+    let registers = [0u64; 13];
+    let pages: [MemoryPage; 0] = [];
+    unsafe {
+      ProgramExecutor::new(
+        program.as_ptr(),
+        program.len(),
+        pages.as_ptr(),
+        0,
+        registers.as_ptr(),
+        10000,
+      )
+      .expect("Failed to create executor")
+    }
+  }
+
+  #[test]
+  fn test_restore_without_next_pc() {
+    let mut builder = ProgramBlobBuilder::new();
+    builder.add_export_by_basic_block(0, b"main");
+    builder.set_code(
+      &[
+        asm::load_imm(Reg::T0, 1),
+        asm::load_imm(Reg::T0, 2),
+        asm::ret(),
+      ],
+      &[],
+    );
+    let program = builder.into_vec();
+    let mut executor = executor_for(&program);
+
+    let mut snapshot = executor.snapshot().unwrap();
+    snapshot.next_pc = None;
+    executor.advance();
+    assert_eq!(executor.current_status, ExecutionStatus::Running);
+
+    // The PC of the stepped executor is not resumed from
+    executor.restore(&snapshot).unwrap();
+    assert!(executor.snapshot().unwrap().next_pc.is_none());
+    executor.advance();
+    assert_eq!(executor.current_status, ExecutionStatus::InstanceRunError);
+
+    // Setting a PC runs again
+    executor.set_next_program_counter(0);
+    executor.run_to_completion();
+    assert_eq!(executor.current_status, ExecutionStatus::Trap);
+    assert_eq!(executor.instance.reg(Reg::T0), 2);
+  }
+
+  #[test]
+  fn test_restore_refused_while_recording() {
+    let mut builder = ProgramBlobBuilder::new();
+    builder.add_export_by_basic_block(0, b"main");
+    builder.set_code(&[asm::ecalli(1), asm::ret()], &[]);
+    let program = builder.into_vec();
+    let mut executor = executor_for(&program);
+
+    let snapshot = executor.snapshot().unwrap();
+    executor.start_recording();
+    executor.run_until_stopped(|executor| {
+      executor.current_status == ExecutionStatus::HostCall
+    });
+    assert_eq!(executor.current_status, ExecutionStatus::HostCall);
+    assert_eq!(
+      executor.restore(&snapshot).err(),
+      Some(SnapshotError::Recording)
+    );
+    assert_eq!(executor.current_status, ExecutionStatus::HostCall);
+
+    let log = executor.take_recording().unwrap();
+    assert_eq!(log.records.len(), 1);
+    executor.restore(&snapshot).unwrap();
+    assert_eq!(executor.current_status, ExecutionStatus::Running);
+  }
+}
@@ -0,0 +1,248 @@
+//! Standard program initialisation, the Y function of the Gray Paper
+//!
+//! Decodes `E_3(|o|) ‖ E_3(|w|) ‖ E_2(z) ‖ E_3(s) ‖ o ‖ w ‖ E_4(|c|) ‖ c` and
+//! lays out read-only data, read-write data and heap, stack and arguments the
+//! same way `src/pvm/memory` does.
+
+use std::{ptr, slice};
+
+use crate::{
+  blob::JamProgram, InitializationError, MemoryRegion, ProgramExecutor,
+};
+
+/// Page size
+pub const Z_P: u32 = 1 << 12;
+/// Major zone size
+pub const Z_Z: u32 = 1 << 16;
+/// Standard input data size
+pub const Z_I: u32 = 1 << 24;
+
+pub const READ_ONLY_BASE_ADDRESS: u32 = Z_Z;
+pub const STACK_BASE_ADDRESS: u32 = (u32::MAX - 2 * Z_Z - Z_I) + 1;
+pub const INPUT_ADDRESS: u32 = (u32::MAX - Z_Z - Z_I) + 1;
+/// Jumping here halts the program, polkavm treats it as return to host
+pub const HALT_ADDRESS: u32 = (u32::MAX - Z_Z) + 1;
+
+const HEADER_LENGTH: usize = 11;
+
+/// The decoded parts of a standard program blob
+#[derive(Debug)]
+pub struct StandardProgram<'a> {
+  pub ro_data: &'a [u8],
+  pub rw_data: &'a [u8],
+  pub heap_pages: u16,
+  pub stack_size: u32,
+  pub code: &'a [u8],
+}
+
+impl<'a> StandardProgram<'a> {
+  pub fn parse(raw: &'a [u8]) -> Option<Self> {
+    let header = raw.get(..HEADER_LENGTH)?;
+    let read_u24 = |bytes: &[u8]| {
+      u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]) as usize
+    };
+
+    let ro_data_size = read_u24(&header[0..3]);
+    let rw_data_size = read_u24(&header[3..6]);
+    let heap_pages = u16::from_le_bytes([header[6], header[7]]);
+    let stack_size = read_u24(&header[8..11]) as u32;
+
+    let mut index = HEADER_LENGTH;
+    let ro_data = raw.get(index..index + ro_data_size)?;
+    index += ro_data_size;
+    let rw_data = raw.get(index..index + rw_data_size)?;
+    index += rw_data_size;
+
+    let code_size =
+      u32::from_le_bytes(raw.get(index..index + 4)?.try_into().ok()?) as usize;
+    index += 4;
+    let code = raw.get(index..index.checked_add(code_size)?)?;
+
+    Some(Self {
+      ro_data,
+      rw_data,
+      heap_pages,
+      stack_size,
+      code,
+    })
+  }
+
+  /// Returns the memory regions with their initial contents
+  ///
+  /// Fails if the layout does not fit the 32-bit address space.
+  pub(crate) fn memory_layout(
+    &self,
+    args: &'a [u8],
+  ) -> Option<Vec<(MemoryRegion, &'a [u8])>> {
+    let ro_size = align_to_page(self.ro_data.len())?;
+    let rw_size = align_to_page(self.rw_data.len())?
+      .checked_add(self.heap_pages as u64 * Z_P as u64)?;
+    let stack_size = align_to_page(self.stack_size as usize)?;
+    let args_size = align_to_page(args.len())?;
+
+    // 5·Z_Z + Z(|o|) + Z(|w| + z·Z_P) + Z(s) + Z_I ≤ 2^32
+    let total = 5 * Z_Z as u64
+      + align_to_zone(self.ro_data.len() as u64)
+      + align_to_zone(
+        self.rw_data.len() as u64 + self.heap_pages as u64 * Z_P as u64,
+      )
+      + align_to_zone(self.stack_size as u64)
+      + Z_I as u64;
+    if total > 1 << 32 || args.len() > Z_I as usize {
+      return None;
+    }
+
+    let rw_address = 2 * Z_Z as u64 + align_to_zone(self.ro_data.len() as u64);
+    let region = |address: u64, size: u64, is_writable: bool| MemoryRegion {
+      address: address as u32,
+      size: size as usize,
+      is_writable,
+    };
+
+    let layout = [
+      (
+        region(READ_ONLY_BASE_ADDRESS as u64, ro_size, false),
+        self.ro_data,
+      ),
+      (region(rw_address, rw_size, true), self.rw_data),
+      (
+        region(STACK_BASE_ADDRESS as u64 - stack_size, stack_size, true),
+        &[][..],
+      ),
+      (region(INPUT_ADDRESS as u64, args_size, false), args),
+    ];
+
+    Some(
+      layout
+        .into_iter()
+        .filter(|(region, _)| region.size > 0)
+        .collect(),
+    )
+  }
+
+  /// Returns the initial registers for an invocation with `args_len` bytes
+  pub fn initial_registers(args_len: usize) -> [u64; 13] {
+    let mut registers = [0u64; 13];
+    registers[0] = HALT_ADDRESS as u64;
+    registers[1] = STACK_BASE_ADDRESS as u64;
+    registers[7] = INPUT_ADDRESS as u64;
+    registers[8] = args_len as u64;
+    registers
+  }
+}
+
+fn align_to_page(size: usize) -> Option<u64> {
+  (size as u64).checked_next_multiple_of(Z_P as u64)
+}
+
+fn align_to_zone(size: u64) -> u64 {
+  size.next_multiple_of(Z_Z as u64)
+}
+
+impl ProgramExecutor {
+  /// Creates an executor for a standard program blob and argument data
+  pub fn from_standard_program(
+    program: &[u8],
+    args: &[u8],
+    gas_limit: u64,
+  ) -> Result<Self, InitializationError> {
+    let program = StandardProgram::parse(program)
+      .ok_or(InitializationError::ProgramFormatError)?;
+    let code = JamProgram::parse(program.code)
+      .map_err(|_| InitializationError::ProgramFormatError)?;
+    let regions = program
+      .memory_layout(args)
+      .ok_or(InitializationError::MemoryError)?;
+
+    Self::from_parts(
+      &code.to_polkavm_blob(),
+      &regions,
+      StandardProgram::initial_registers(args.len()),
+      gas_limit,
+    )
+  }
+}
+
+/// Creates a program executor from a JAM standard program blob
+///
+/// The blob must not carry the metadata prefix. Memory, stack and registers
+/// are initialised according to the Y function, execution starts at PC 0.
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts raw pointers as input
+#[no_mangle]
+pub unsafe extern "C" fn create_executor_from_standard_program(
+  program: *const u8,
+  program_len: usize,
+  args: *const u8,
+  args_len: usize,
+  gas_limit: u64,
+) -> *mut ProgramExecutor {
+  let program = slice::from_raw_parts(program, program_len);
+  let args = if args.is_null() {
+    &[][..]
+  } else {
+    slice::from_raw_parts(args, args_len)
+  };
+
+  match ProgramExecutor::from_standard_program(program, args, gas_limit) {
+    Ok(executor) => Box::into_raw(Box::new(executor)),
+    Err(_) => ptr::null_mut(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn encode_standard_program(
+    ro_data: &[u8],
+    rw_data: &[u8],
+    heap_pages: u16,
+    stack_size: u32,
+    code: &[u8],
+  ) -> Vec<u8> {
+    let mut raw = Vec::new();
+    raw.extend_from_slice(&(ro_data.len() as u32).to_le_bytes()[..3]);
+    raw.extend_from_slice(&(rw_data.len() as u32).to_le_bytes()[..3]);
+    raw.extend_from_slice(&heap_pages.to_le_bytes());
+    raw.extend_from_slice(&stack_size.to_le_bytes()[..3]);
+    raw.extend_from_slice(ro_data);
+    raw.extend_from_slice(rw_data);
+    raw.extend_from_slice(&(code.len() as u32).to_le_bytes());
+    raw.extend_from_slice(code);
+    raw
+  }
+
+  #[test]
+  fn test_standard_program_layout() {
+    // A single `trap` instruction
+    let code = [0, 0, 1, 0, 1];
+    let raw = encode_standard_program(&[1, 2, 3], &[4, 5], 2, 4096, &code);
+    let program = StandardProgram::parse(&raw).unwrap();
+    assert_eq!(program.ro_data, &[1, 2, 3]);
+    assert_eq!(program.rw_data, &[4, 5]);
+    assert_eq!(program.heap_pages, 2);
+
+    let layout = program.memory_layout(&[9; 10]).unwrap();
+    let addresses: Vec<_> = layout
+      .iter()
+      .map(|(region, _)| (region.address, region.size, region.is_writable))
+      .collect();
+    assert_eq!(
+      addresses,
+      vec![
+        (0x10000, 0x1000, false),
+        (0x30000, 0x3000, true),
+        (0xfefdf000, 0x1000, true),
+        (0xfeff0000, 0x1000, false),
+      ]
+    );
+
+    let executor =
+      ProgramExecutor::from_standard_program(&raw, &[9; 10], 1000).unwrap();
+    assert_eq!(executor.instance.read_u32(0x10000).unwrap(), 0x030201);
+    assert_eq!(executor.instance.read_u8(0xfeff0009).unwrap(), 9);
+  }
+}
@@ -0,0 +1,286 @@
+//! Direct access to the state of a live executor
+//!
+//! Used to emulate host calls (setting ω7/ω8 and writing output memory)
+//! and for targeted debugging without going through `step_executor`.
+
+use std::slice;
+
+use polkavm::{ProgramCounter, Reg};
+
+use crate::ProgramExecutor;
+
+#[repr(C)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum StateError {
+  InvalidRegister = 1,
+  InaccessibleMemory = 2,
+  MemoryError = 3,
+}
+
+impl ProgramExecutor {
+  pub fn register(&self, index: u32) -> Result<u64, StateError> {
+    let reg = Reg::from_raw(index).ok_or(StateError::InvalidRegister)?;
+    Ok(self.instance.reg(reg))
+  }
+
+  pub fn set_register(
+    &mut self,
+    index: u32,
+    value: u64,
+  ) -> Result<(), StateError> {
+    let reg = Reg::from_raw(index).ok_or(StateError::InvalidRegister)?;
+    self.instance.set_reg(reg, value);
+    Ok(())
+  }
+
+  /// Sets the program counter execution continues from on the next step
+  pub fn set_next_program_counter(&mut self, pc: u32) {
+    self.instance.set_next_program_counter(ProgramCounter(pc));
+    self.step_pc = None;
+    self.pc_unset = false;
+  }
+
+  pub fn gas(&self) -> i64 {
+    self.instance.gas()
+  }
+
+  pub fn set_gas(&mut self, gas: i64) {
+    self.instance.set_gas(gas);
+  }
+
+  /// Reads memory, every page in the range must be mapped
+  pub fn read_memory_into(
+    &self,
+    address: u32,
+    buffer: &mut [u8],
+  ) -> Result<(), StateError> {
+    let length = u32::try_from(buffer.len())
+      .map_err(|_| StateError::InaccessibleMemory)?;
+    if !self.page_map.is_accessible(address, length, false) {
+      return Err(StateError::InaccessibleMemory);
+    }
+
+    self
+      .instance
+      .read_memory_into(address, buffer)
+      .map(|_| ())
+      .map_err(|_| StateError::MemoryError)
+  }
+
+  /// Writes memory, every page in the range must be mapped writable
+  ///
+  /// Read-only pages are rejected, so writes through here see the same
+  /// protection as the guest does.
+  pub fn write_memory(
+    &mut self,
+    address: u32,
+    data: &[u8],
+  ) -> Result<(), StateError> {
+    let length =
+      u32::try_from(data.len()).map_err(|_| StateError::InaccessibleMemory)?;
+    if !self.page_map.is_accessible(address, length, true) {
+      return Err(StateError::InaccessibleMemory);
+    }
+
+    self
+      .instance
+      .write_memory(address, data)
+      .map_err(|_| StateError::MemoryError)?;
+    self.record_memory_write(address, data);
+    Ok(())
+  }
+}
+
+fn status_code(result: Result<(), StateError>) -> i32 {
+  match result {
+    Ok(()) => 0,
+    Err(err) => err as i32,
+  }
+}
+
+/// Reads a single register into `value_out`
+///
+/// Returns 0 on success or a `StateError` code
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts raw pointers as input
+#[no_mangle]
+pub unsafe extern "C" fn executor_get_register(
+  executor: *const ProgramExecutor,
+  index: u32,
+  value_out: *mut u64,
+) -> i32 {
+  status_code((&*executor).register(index).map(|value| *value_out = value))
+}
+
+/// Writes a single register
+///
+/// Returns 0 on success or a `StateError` code
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts a raw pointer as input
+#[no_mangle]
+pub unsafe extern "C" fn executor_set_register(
+  executor: *mut ProgramExecutor,
+  index: u32,
+  value: u64,
+) -> i32 {
+  status_code((&mut *executor).set_register(index, value))
+}
+
+/// Sets the program counter the next step starts from
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts a raw pointer as input
+#[no_mangle]
+pub unsafe extern "C" fn executor_set_next_pc(
+  executor: *mut ProgramExecutor,
+  pc: u32,
+) {
+  (&mut *executor).set_next_program_counter(pc);
+}
+
+/// Returns the remaining gas
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts a raw pointer as input
+#[no_mangle]
+pub unsafe extern "C" fn executor_get_gas(
+  executor: *const ProgramExecutor,
+) -> i64 {
+  (&*executor).gas()
+}
+
+/// Sets the remaining gas
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts a raw pointer as input
+#[no_mangle]
+pub unsafe extern "C" fn executor_set_gas(
+  executor: *mut ProgramExecutor,
+  gas: i64,
+) {
+  (&mut *executor).set_gas(gas);
+}
+
+/// Reads `len` bytes at `address` into `buffer`
+///
+/// `buffer` may be null when `len` is 0.
+/// Returns 0 on success or a `StateError` code
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts raw pointers as input
+#[no_mangle]
+pub unsafe extern "C" fn executor_read_memory(
+  executor: *const ProgramExecutor,
+  address: u32,
+  buffer: *mut u8,
+  len: usize,
+) -> i32 {
+  if len == 0 {
+    return 0;
+  }
+  let buffer = slice::from_raw_parts_mut(buffer, len);
+  status_code((&*executor).read_memory_into(address, buffer))
+}
+
+/// Writes `len` bytes from `data` at `address`
+///
+/// Every page in the range must be mapped writable, read-only pages are
+/// reported as `InaccessibleMemory` like unmapped ones. `data` may be null
+/// when `len` is 0.
+/// Returns 0 on success or a `StateError` code
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts raw pointers as input
+#[no_mangle]
+pub unsafe extern "C" fn executor_write_memory(
+  executor: *mut ProgramExecutor,
+  address: u32,
+  data: *const u8,
+  len: usize,
+) -> i32 {
+  if len == 0 {
+    return 0;
+  }
+  let data = slice::from_raw_parts(data, len);
+  status_code((&mut *executor).write_memory(address, data))
+}
+
+#[cfg(test)]
+mod tests {
+  use polkavm_common::{program::asm, writer::ProgramBlobBuilder};
+
+  use super::*;
+  use crate::{ExecutionStatus, MemoryPage};
+
+  #[test]
+  fn test_state_accessors() {
+    let mut builder = ProgramBlobBuilder::new();
+    builder.add_export_by_basic_block(0, b"main");
+    builder.set_code(
+      &[
+        asm::load_indirect_u32(Reg::T1, Reg::A0, 0),
+        asm::add_64(Reg::T0, Reg::T1, Reg::A1),
+        asm::ret(),
+      ],
+      &[],
+    );
+    let program = builder.into_vec();
+    let mut memory = vec![0u8; 4096];
+    let page = MemoryPage {
+      address: 0x20000,
+      data: memory.as_mut_ptr(),
+      size: 4096,
+      is_writable: true,
+    };
+    let registers = [0u64; 13];
+
+    unsafe {
+      let mut executor = ProgramExecutor::new(
+        program.as_ptr(),
+        program.len(),
+        &page,
+        1,
+        registers.as_ptr(),
+        10000,
+      )
+      .expect("Failed to create executor");
+
+      executor.set_register(7, 0x20010).unwrap();
+      executor.set_register(8, 2).unwrap();
+      executor
+        .write_memory(0x20010, &40u32.to_le_bytes())
+        .unwrap();
+      executor.set_gas(500);
+      assert_eq!(
+        executor.set_register(13, 0),
+        Err(StateError::InvalidRegister)
+      );
+      assert_eq!(
+        executor.write_memory(0x30000, &[1]),
+        Err(StateError::InaccessibleMemory)
+      );
+      assert_eq!(
+        executor_write_memory(&mut executor, 0x30000, std::ptr::null(), 0),
+        0
+      );
+
+      executor.run_to_completion();
+      assert_eq!(executor.current_status, ExecutionStatus::Trap);
+      assert_eq!(executor.register(2).unwrap(), 42);
+      assert!(executor.gas() < 500);
+
+      let mut buffer = [0u8; 4];
+      executor.read_memory_into(0x20010, &mut buffer).unwrap();
+      assert_eq!(u32::from_le_bytes(buffer), 40);
+    }
+  }
+}
@@ -0,0 +1,349 @@
+//! Records executions in the w3f jamtestvectors PVM JSON schema
+//!
+//! The program is given in the JAM deblob format, as in the test vectors,
+//! so any recorded run can be fed to our `src/pvm_test.zig` runner with
+//! polkavm acting as the oracle.
+
+use std::{ffi::CStr, fs, os::raw::c_char, slice};
+
+use polkavm::Reg;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  blob::JamProgram, memory_map::MappedRange, memory_regions, ExecutionStatus,
+  InitializationError, MemoryPage, MemoryRegion, ProgramExecutor,
+};
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PageMapEntry {
+  pub address: u32,
+  pub length: u32,
+  pub is_writable: bool,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct MemoryChunk {
+  pub address: u32,
+  pub contents: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Status {
+  Panic,
+  Halt,
+  PageFault,
+  OutOfGas,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TestVector {
+  pub name: String,
+  pub initial_regs: [u64; 13],
+  pub initial_pc: u32,
+  pub initial_page_map: Vec<PageMapEntry>,
+  pub initial_memory: Vec<MemoryChunk>,
+  pub initial_gas: i64,
+  pub program: Vec<u8>,
+  pub expected_status: Status,
+  pub expected_regs: [u64; 13],
+  pub expected_pc: u32,
+  pub expected_memory: Vec<MemoryChunk>,
+  pub expected_gas: i64,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub expected_page_fault_address: Option<u32>,
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RecordError {
+  InvalidArgument = 1,
+  InitializationError = 2,
+  ExecutionError = 3,
+  SerializationError = 4,
+  IoError = 5,
+}
+
+impl TestVector {
+  /// Runs `program` to completion on polkavm and records the outcome
+  pub fn record(
+    name: &str,
+    program: &[u8],
+    pages: &[(MemoryRegion, &[u8])],
+    registers: [u64; 13],
+    gas: i64,
+  ) -> Result<Self, RecordError> {
+    Self::record_from(name, program, pages, registers, gas, 0)
+      .map(|(vector, _)| vector)
+  }
+
+  /// Runs the initial state of this vector on polkavm and records what it
+  /// does, so the expected fields of the result are polkavm's outcome
+  ///
+  /// The memory map the executor ended up with is returned alongside, the
+  /// page map of the result only repeats the input.
+  pub fn replay(&self) -> Result<(Self, Vec<MappedRange>), RecordError> {
+    let buffers = memory_image(&self.initial_page_map, &self.initial_memory);
+    let pages: Vec<(MemoryRegion, &[u8])> = self
+      .initial_page_map
+      .iter()
+      .zip(&buffers)
+      .map(|(entry, (_, data))| {
+        (
+          MemoryRegion {
+            address: entry.address,
+            size: entry.length as usize,
+            is_writable: entry.is_writable,
+          },
+          &data[..],
+        )
+      })
+      .collect();
+
+    Self::record_from(
+      &self.name,
+      &self.program,
+      &pages,
+      self.initial_regs,
+      self.initial_gas,
+      self.initial_pc,
+    )
+  }
+
+  fn record_from(
+    name: &str,
+    program: &[u8],
+    pages: &[(MemoryRegion, &[u8])],
+    registers: [u64; 13],
+    gas: i64,
+    initial_pc: u32,
+  ) -> Result<(Self, Vec<MappedRange>), RecordError> {
+    let code = JamProgram::parse(program)
+      .map_err(|_| RecordError::InitializationError)?;
+    let mut executor = ProgramExecutor::from_parts(
+      &code.to_polkavm_blob(),
+      pages,
+      registers,
+      gas as u64,
+    )
+    .map_err(|_: InitializationError| RecordError::InitializationError)?;
+    executor.set_next_program_counter(initial_pc);
+
+    executor.run_to_completion();
+
+    let expected_status = match executor.current_status {
+      ExecutionStatus::Success => Status::Halt,
+      ExecutionStatus::Trap => Status::Panic,
+      ExecutionStatus::OutOfGas => Status::OutOfGas,
+      ExecutionStatus::Segfault => Status::PageFault,
+      ExecutionStatus::InstanceRunError
+      | ExecutionStatus::Running
+      | ExecutionStatus::Interrupted
+      | ExecutionStatus::HostCall => return Err(RecordError::ExecutionError),
+    };
+
+    let mut expected_regs = [0u64; 13];
+    for (i, value) in expected_regs.iter_mut().enumerate() {
+      if let Some(reg) = Reg::from_raw(i as u32) {
+        *value = executor.instance.reg(reg);
+      }
+    }
+
+    let mut expected_memory = Vec::new();
+    for (region, _) in pages {
+      let contents = executor
+        .instance
+        .read_memory(region.address, region.size as u32)
+        .map_err(|_| RecordError::ExecutionError)?;
+      expected_memory.extend(non_zero_chunk(region.address, contents));
+    }
+
+    let vector = Self {
+      name: name.to_owned(),
+      initial_regs: registers,
+      initial_pc,
+      initial_page_map: pages
+        .iter()
+        .map(|(region, _)| PageMapEntry {
+          address: region.address,
+          length: region.size as u32,
+          is_writable: region.is_writable,
+        })
+        .collect(),
+      initial_memory: pages
+        .iter()
+        .filter_map(|(region, data)| {
+          non_zero_chunk(region.address, data.to_vec())
+        })
+        .collect(),
+      initial_gas: gas,
+      program: program.to_vec(),
+      expected_status,
+      expected_regs,
+      expected_pc: executor.instance.program_counter().map_or(0, |pc| pc.0),
+      expected_memory,
+      expected_gas: executor.instance.gas(),
+      expected_page_fault_address: (expected_status == Status::PageFault)
+        .then_some(executor.segfault_address),
+    };
+    Ok((vector, executor.memory_map()))
+  }
+}
+
+/// Trims leading and trailing zeros, all-zero memory is left out of the
+/// vector since unwritten pages read as zero anyway
+fn non_zero_chunk(address: u32, contents: Vec<u8>) -> Option<MemoryChunk> {
+  let start = contents.iter().position(|&byte| byte != 0)?;
+  let end = contents.iter().rposition(|&byte| byte != 0)? + 1;
+  Some(MemoryChunk {
+    address: address + start as u32,
+    contents: contents[start..end].to_vec(),
+  })
+}
+
+/// Copies the part of `chunk` that falls inside the memory starting at
+/// `address`
+fn copy_overlap(address: u32, data: &mut [u8], chunk: &MemoryChunk) {
+  let start = chunk.address.max(address) as u64;
+  let end = (chunk.address as u64 + chunk.contents.len() as u64)
+    .min(address as u64 + data.len() as u64);
+  if start >= end {
+    return;
+  }
+  let (from, to) = (
+    (start - chunk.address as u64) as usize,
+    (end - chunk.address as u64) as usize,
+  );
+  let at = (start - address as u64) as usize;
+  data[at..at + to - from].copy_from_slice(&chunk.contents[from..to]);
+}
+
+/// Lays `chunks` out over the regions of `page_map`, with zeros wherever no
+/// chunk was given
+pub fn memory_image(
+  page_map: &[PageMapEntry],
+  chunks: &[MemoryChunk],
+) -> Vec<(u32, Vec<u8>)> {
+  page_map
+    .iter()
+    .map(|entry| {
+      let mut data = vec![0u8; entry.length as usize];
+      for chunk in chunks {
+        copy_overlap(entry.address, &mut data, chunk);
+      }
+      (entry.address, data)
+    })
+    .collect()
+}
+
+/// Runs a JAM program to completion and writes the run as a PVM test vector
+/// JSON file to `output_path`
+///
+/// Returns 0 on success or a `RecordError` code
+///
+/// # Safety
+///
+/// This function is unsafe because it accepts raw pointers as input
+#[no_mangle]
+pub unsafe extern "C" fn record_test_vector(
+  name: *const c_char,
+  program: *const u8,
+  program_len: usize,
+  initial_pages: *const MemoryPage,
+  page_count: usize,
+  initial_registers: *const u64,
+  gas_limit: i64,
+  output_path: *const c_char,
+) -> i32 {
+  let (Ok(name), Ok(output_path)) = (
+    CStr::from_ptr(name).to_str(),
+    CStr::from_ptr(output_path).to_str(),
+  ) else {
+    return RecordError::InvalidArgument as i32;
+  };
+
+  let program = slice::from_raw_parts(program, program_len);
+  let pages = memory_regions(initial_pages, page_count);
+  let mut registers = [0u64; 13];
+  registers.copy_from_slice(slice::from_raw_parts(initial_registers, 13));
+
+  let result = TestVector::record(name, program, &pages, registers, gas_limit)
+    .and_then(|vector| {
+      serde_json::to_string_pretty(&vector)
+        .map_err(|_| RecordError::SerializationError)
+    })
+    .and_then(|json| {
+      fs::write(output_path, json).map_err(|_| RecordError::IoError)
+    });
+
+  match result {
+    Ok(()) => 0,
+    Err(err) => err as i32,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_record_inst_add_32() {
+    // The `inst_add_32` program from the PVM test vectors
+    let program = [0, 0, 3, 190, 135, 9, 1];
+    let mut registers = [0u64; 13];
+    registers[7] = 1;
+    registers[8] = 2;
+
+    let vector =
+      TestVector::record("inst_add_32", &program, &[], registers, 10000)
+        .unwrap();
+    assert_eq!(vector.expected_status, Status::Panic);
+    assert_eq!(vector.expected_regs[9], 3);
+    assert!(vector.expected_memory.is_empty());
+
+    let json = serde_json::to_string(&vector).unwrap();
+    assert!(json.contains("\"expected-status\":\"panic\""));
+    assert!(!json.contains("expected-page-fault-address"));
+    assert_eq!(serde_json::from_str::<TestVector>(&json).unwrap(), vector);
+  }
+
+  #[test]
+  fn test_replay_matches_record() {
+    let program = [0, 0, 3, 190, 135, 9, 1];
+    let mut data = vec![0u8; 4096];
+    data[8..12].copy_from_slice(&[1, 2, 3, 4]);
+    let region = MemoryRegion {
+      address: 0x20000,
+      size: 4096,
+      is_writable: true,
+    };
+    let mut registers = [0u64; 13];
+    registers[7] = 5;
+    registers[8] = 6;
+
+    let vector = TestVector::record(
+      "inst_add_32",
+      &program,
+      &[(region, &data[..])],
+      registers,
+      10000,
+    )
+    .unwrap();
+    let (replayed, memory_map) = vector.replay().unwrap();
+    assert_eq!(replayed, vector);
+    assert_eq!(
+      memory_map,
+      vec![MappedRange {
+        address: 0x20000,
+        length: 4096,
+        is_writable: true,
+        is_dynamic: false,
+      }]
+    );
+
+    let image = memory_image(&vector.initial_page_map, &vector.initial_memory);
+    assert_eq!(image, vec![(0x20000, data)]);
+  }
+}
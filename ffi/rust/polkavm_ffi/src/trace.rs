@@ -0,0 +1,211 @@
+use std::io::Write;
+
+use crate::{
+  coverage,
+  instructions::{memory_access, operands, DecodedInstruction},
+  ProgramExecutor,
+};
+
+const TRACE_TEXT_LENGTH: usize = 64;
+
+/// Record of the instruction executed by a single step
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct StepTrace {
+  /// False when the step did not execute an instruction, e.g. the first step
+  /// which only enters the program
+  pub(crate) executed: bool,
+  pub(crate) opcode: u8,
+  pub(crate) pc_before: u32,
+  pub(crate) pc_after: u32,
+  pub(crate) gas_charged: i64,
+  pub(crate) has_memory_access: bool,
+  pub(crate) memory_is_write: bool,
+  pub(crate) memory_address: u32,
+  pub(crate) memory_size: u32,
+  /// Registers named by the instruction, in operand order
+  pub(crate) registers: [u8; 3],
+  pub(crate) register_count: u8,
+  /// Immediates and branch targets of the instruction, in operand order
+  pub(crate) immediates: [u64; 2],
+  pub(crate) immediate_count: u8,
+  /// Disassembly of the instruction, truncated to fit
+  pub(crate) text: [u8; TRACE_TEXT_LENGTH],
+  pub(crate) text_len: usize,
+}
+
+impl StepTrace {
+  pub const fn empty() -> Self {
+    Self {
+      executed: false,
+      opcode: 0,
+      pc_before: 0,
+      pc_after: 0,
+      gas_charged: 0,
+      has_memory_access: false,
+      memory_is_write: false,
+      memory_address: 0,
+      memory_size: 0,
+      registers: [0; 3],
+      register_count: 0,
+      immediates: [0; 2],
+      immediate_count: 0,
+      text: [0; TRACE_TEXT_LENGTH],
+      text_len: 0,
+    }
+  }
+
+  pub fn text(&self) -> &str {
+    std::str::from_utf8(&self.text[..self.text_len]).unwrap_or_default()
+  }
+
+  pub fn registers(&self) -> &[u8] {
+    &self.registers[..self.register_count as usize]
+  }
+
+  pub fn immediates(&self) -> &[u64] {
+    &self.immediates[..self.immediate_count as usize]
+  }
+}
+
+/// State captured right before an instruction is executed
+pub(crate) struct PendingTrace {
+  pc: u32,
+  instruction: DecodedInstruction,
+  gas: i64,
+  trace: StepTrace,
+}
+
+impl ProgramExecutor {
+  /// Captures the instruction the upcoming step executes, if known
+  pub(crate) fn begin_step_trace(&mut self) -> Option<PendingTrace> {
+    let pc = self.step_pc?;
+    let instruction = *self.instructions.get(pc)?;
+    coverage::record(
+      &mut self.pc_coverage,
+      pc,
+      &instruction.kind,
+      &self.instance,
+    );
+
+    let mut trace = StepTrace::empty();
+    if let Some(access) = memory_access(&instruction.kind, &self.instance) {
+      trace.has_memory_access = true;
+      trace.memory_is_write = access.is_write;
+      trace.memory_address = access.address;
+      trace.memory_size = access.size;
+    }
+
+    Some(PendingTrace {
+      pc,
+      instruction,
+      gas: self.instance.gas(),
+      trace,
+    })
+  }
+
+  /// Completes the trace record once the step has run
+  pub(crate) fn finish_step_trace(
+    &self,
+    pending: Option<PendingTrace>,
+  ) -> StepTrace {
+    let Some(pending) = pending else {
+      return StepTrace::empty();
+    };
+
+    let mut trace = pending.trace;
+    trace.executed = true;
+    trace.opcode = pending.instruction.kind.opcode() as u8;
+    trace.pc_before = pending.pc;
+    trace.pc_after = self
+      .instance
+      .program_counter()
+      .map_or(pending.instruction.next_pc, |pc| pc.0);
+    trace.gas_charged = pending.gas - self.instance.gas();
+
+    let operands = operands(&pending.instruction.kind);
+    trace.registers = operands.registers;
+    trace.register_count = operands.register_count;
+    trace.immediates = operands.immediates;
+    trace.immediate_count = operands.immediate_count;
+
+    let mut text = &mut trace.text[..];
+    // Overlong disassembly is truncated to the buffer
+    let _ = write!(text, "{}", pending.instruction.kind);
+    trace.text_len = TRACE_TEXT_LENGTH - text.len();
+
+    trace
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use polkavm::Reg;
+  use polkavm_common::{program::asm, writer::ProgramBlobBuilder};
+
+  use crate::MemoryPage;
+
+  use super::*;
+
+  #[test]
+  fn test_step_trace_records() {
+    let mut builder = ProgramBlobBuilder::new();
+    builder.add_export_by_basic_block(0, b"main");
+    builder.set_code(
+      &[
+        asm::store_imm_u32(0x20000, 0x12345678),
+        asm::load_imm(Reg::T0, 0xdeadbeef),
+        asm::ret(),
+      ],
+      &[],
+    );
+    let program = builder.into_vec();
+    let mut memory = vec![0u8; 4096];
+    let page = MemoryPage {
+      address: 0x20000,
+      data: memory.as_mut_ptr(),
+      size: 4096,
+      is_writable: true,
+    };
+    let registers = [0u64; 13];
+
+    unsafe {
+      let mut executor = ProgramExecutor::new(
+        program.as_ptr(),
+        program.len(),
+        &page,
+        1,
+        registers.as_ptr(),
+        10000,
+      )
+      .expect("Failed to create executor");
+
+      let mut traces = Vec::new();
+      while !executor.is_finished() {
+        executor.advance();
+        traces.push(executor.last_trace);
+      }
+
+      // The first step only enters the program
+      assert!(!traces[0].executed);
+
+      let store = &traces[1];
+      assert!(store.executed);
+      assert_eq!(store.pc_before, 0);
+      assert!(store.pc_after > 0);
+      assert!(store.gas_charged > 0);
+      assert!(store.has_memory_access && store.memory_is_write);
+      assert_eq!(store.memory_address, 0x20000);
+      assert_eq!(store.memory_size, 4);
+      assert!(!store.text().is_empty());
+      assert!(store.registers().is_empty());
+      assert_eq!(store.immediates(), &[0x20000, 0x12345678]);
+
+      let load = &traces[2];
+      assert_eq!(load.pc_before, store.pc_after);
+      assert!(!load.has_memory_access);
+      assert_eq!(load.registers(), &[Reg::T0 as u8]);
+      assert_eq!(load.immediates(), &[0xdeadbeef]);
+    }
+  }
+}
@@ -0,0 +1,160 @@
+//! Runs the w3f jamtestvectors PVM cases through polkavm
+//!
+//! Every vector is replayed on `ProgramExecutor` and polkavm's outcome is
+//! compared with the expected fields, which shows where the oracle itself
+//! differs from the spec. Gas is compared but only reported unless
+//! `PVM_VECTORS_CHECK_GAS` is set, since polkavm's gas model does not follow
+//! the spec yet.
+//!
+//! The vectors are read from the `src/jamtestvectors` submodule, or from the
+//! directory in `PVM_VECTORS_DIR`. The suite is skipped when the submodule is
+//! not checked out, but fails if `PVM_VECTORS_DIR` is set or the directory
+//! holds no vectors.
+//!
+//! Needs the `test-vectors` feature: `cargo test --features test-vectors`.
+
+use std::{env, fs, path::PathBuf};
+
+use polkavm_ffi::{
+  memory_map::{MappedRange, PAGE_SIZE},
+  test_vector::{memory_image, TestVector},
+};
+
+fn vectors_dir() -> PathBuf {
+  env::var_os("PVM_VECTORS_DIR")
+    .map(PathBuf::from)
+    .unwrap_or_else(|| {
+      PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("../../../src/jamtestvectors/pulls/pvm/pvm/programs")
+    })
+}
+
+/// Splits a range into its pages and their access
+fn pages(address: u32, length: u32, is_writable: bool) -> Vec<(u32, bool)> {
+  let first = address / PAGE_SIZE;
+  let last = (address as u64 + length as u64).div_ceil(PAGE_SIZE as u64);
+  (first as u64..last)
+    .map(|page| (page as u32 * PAGE_SIZE, is_writable))
+    .collect()
+}
+
+/// Differences between the vector and polkavm's run, gas is kept apart
+fn compare(
+  expected: &TestVector,
+  actual: &TestVector,
+  memory_map: &[MappedRange],
+) -> (Vec<String>, bool) {
+  let mut errors = Vec::new();
+  if expected.expected_status != actual.expected_status {
+    errors.push(format!(
+      "status {:?}, expected {:?}",
+      actual.expected_status, expected.expected_status
+    ));
+  }
+  for (i, (want, got)) in expected
+    .expected_regs
+    .iter()
+    .zip(&actual.expected_regs)
+    .enumerate()
+  {
+    if want != got {
+      errors.push(format!("r{i} = {got:#x}, expected {want:#x}"));
+    }
+  }
+  if expected.expected_pc != actual.expected_pc {
+    errors.push(format!(
+      "pc {}, expected {}",
+      actual.expected_pc, expected.expected_pc
+    ));
+  }
+  let mut want_pages: Vec<_> = expected
+    .initial_page_map
+    .iter()
+    .flat_map(|entry| pages(entry.address, entry.length, entry.is_writable))
+    .collect();
+  want_pages.sort();
+  let got_pages: Vec<_> = memory_map
+    .iter()
+    .flat_map(|range| pages(range.address, range.length, range.is_writable))
+    .collect();
+  if want_pages != got_pages {
+    errors.push("page map was not set up as given".to_owned());
+  }
+  let want =
+    memory_image(&expected.initial_page_map, &expected.expected_memory);
+  let got = memory_image(&actual.initial_page_map, &actual.expected_memory);
+  for ((address, want), (_, got)) in want.iter().zip(&got) {
+    if let Some(offset) = want.iter().zip(got).position(|(a, b)| a != b) {
+      errors.push(format!(
+        "memory differs at {:#x}",
+        *address as usize + offset
+      ));
+    }
+  }
+  if expected.expected_page_fault_address != actual.expected_page_fault_address
+  {
+    errors.push(format!(
+      "page fault address {:?}, expected {:?}",
+      actual.expected_page_fault_address, expected.expected_page_fault_address
+    ));
+  }
+
+  (errors, expected.expected_gas == actual.expected_gas)
+}
+
+#[test]
+fn test_pvm_vectors() {
+  let dir = vectors_dir();
+  let dir_is_given = env::var_os("PVM_VECTORS_DIR").is_some();
+  let entries = match fs::read_dir(&dir) {
+    Ok(entries) => entries,
+    Err(err) if dir_is_given => {
+      panic!("no PVM test vectors at {}: {err}", dir.display())
+    }
+    Err(_) => {
+      eprintln!("skipping, no PVM test vectors at {}", dir.display());
+      return;
+    }
+  };
+
+  let mut paths: Vec<_> = entries
+    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+    .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+    .collect();
+  paths.sort();
+  assert!(
+    !paths.is_empty(),
+    "no PVM test vectors in {}",
+    dir.display()
+  );
+
+  let check_gas = env::var_os("PVM_VECTORS_CHECK_GAS").is_some();
+  let mut failures = Vec::new();
+  let mut gas_mismatches = 0;
+  for path in &paths {
+    let json = fs::read_to_string(path).unwrap();
+    let vector: TestVector = serde_json::from_str(&json)
+      .unwrap_or_else(|err| panic!("{}: {err}", path.display()));
+
+    let (mut errors, gas_matches) = match vector.replay() {
+      Ok((actual, memory_map)) => compare(&vector, &actual, &memory_map),
+      Err(err) => (vec![format!("failed to run: {err:?}")], true),
+    };
+    if !gas_matches {
+      gas_mismatches += 1;
+      if check_gas {
+        errors.push("gas differs".to_owned());
+      }
+    }
+    if !errors.is_empty() {
+      failures.push(format!("{}: {}", vector.name, errors.join(", ")));
+    }
+  }
+
+  eprintln!(
+    "{} of {} PVM vectors differ, {gas_mismatches} in gas",
+    failures.len(),
+    paths.len()
+  );
+  assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
@@ -35,11 +35,61 @@ extern fn bandersnatch_verify(
     output_hash_out: [*]u8,
 ) c_int;
 
+extern fn bandersnatch_vrf_output(
+    secret: [*]const u8,
+    vrf_input_data: [*]const u8,
+    vrf_input_len: usize,
+    output_hash_out: [*]u8,
+) c_int;
+
 extern fn bandersnatch_output_hash(
     signature: [*]const u8,
     output_hash_out: [*]u8,
 ) c_int;
 
+extern fn bandersnatch_verify_block_seal(
+    public_key: [*]const u8,
+    unsealed_header: [*]const u8,
+    unsealed_header_len: usize,
+    entropy: [*]const u8,
+    sealed_with_ticket: bool,
+    ticket_attempt: u8,
+    seal: [*]const u8,
+    entropy_source: [*]const u8,
+    seal_output_out: [*]u8,
+    entropy_output_out: [*]u8,
+) c_int;
+
+extern fn bandersnatch_verify_seal(
+    public_key: [*]const u8,
+    unsealed_header: [*]const u8,
+    unsealed_header_len: usize,
+    entropy: [*]const u8,
+    sealed_with_ticket: bool,
+    ticket_attempt: u8,
+    seal: [*]const u8,
+    seal_output_out: [*]u8,
+) c_int;
+
+extern fn bandersnatch_verify_entropy_source(
+    public_key: [*]const u8,
+    seal_output: [*]const u8,
+    entropy_source: [*]const u8,
+    entropy_output_out: [*]u8,
+) c_int;
+
+extern fn bandersnatch_sign_block_seal(
+    secret: [*]const u8,
+    unsealed_header: [*]const u8,
+    unsealed_header_len: usize,
+    entropy_source_offset: usize,
+    entropy: [*]const u8,
+    sealed_with_ticket: bool,
+    ticket_attempt: u8,
+    entropy_source_out: [*]u8,
+    seal_out: [*]u8,
+) c_int;
+
 /// Bandersnatch VRF (Verifiable Random Function) implementation
 pub const Bandersnatch = struct {
     /// Length (in bytes) of a secret key
@@ -200,7 +250,159 @@ pub const Bandersnatch = struct {
             if (rc != 0) return Error.SigningFailed;
             return Signature.fromBytes(sig_bytes);
         }
+
+        /// Sign the entropy source H_v and the seal H_s of a block. H_v is
+        /// placed at `entropy_source_offset` in the unsealed header encoding
+        /// before the header is sealed, the bytes there are ignored.
+        pub fn signBlockSeal(
+            key_pair: KeyPair,
+            unsealed_header: []const u8,
+            entropy_source_offset: usize,
+            entropy: [32]u8,
+            mode: SealMode,
+        ) Error!BlockSeal {
+            var block_seal: BlockSeal = undefined;
+            const rc = bandersnatch_sign_block_seal(
+                &key_pair.secret_key.bytes,
+                unsealed_header.ptr,
+                unsealed_header.len,
+                entropy_source_offset,
+                &entropy,
+                mode == .ticket,
+                switch (mode) {
+                    .ticket => |attempt| attempt,
+                    .fallback => 0,
+                },
+                &block_seal.entropy_source.bytes,
+                &block_seal.seal.bytes,
+            );
+            if (rc != 0) return Error.SigningFailed;
+            return block_seal;
+        }
+
+        /// VRF output hash for a message without creating a proof, equal to
+        /// the output of any signature over the same message
+        pub fn vrfOutput(key_pair: KeyPair, msg: []const u8) Error![output_length]u8 {
+            var output: [output_length]u8 = undefined;
+            const rc = bandersnatch_vrf_output(
+                &key_pair.secret_key.bytes,
+                msg.ptr,
+                msg.len,
+                &output,
+            );
+            if (rc != 0) return Error.OutputHashFailed;
+            return output;
+        }
+    };
+
+    /// How a block was sealed: with a ticket and its attempt index, or with
+    /// the author's key in fallback mode
+    pub const SealMode = union(enum) {
+        ticket: u8,
+        fallback,
     };
+
+    /// VRF output hashes Y(H_s) and Y(H_v) of a verified block seal
+    pub const SealOutputs = struct {
+        seal: [output_length]u8,
+        entropy_source: [output_length]u8,
+    };
+
+    /// Signatures H_v and H_s of an authored block
+    pub const BlockSeal = struct {
+        entropy_source: Signature,
+        seal: Signature,
+    };
+
+    pub const SealError = error{
+        InvalidSealInput,
+        SealVerificationFailed,
+        EntropySourceVerificationFailed,
+    };
+
+    /// Verify the seal H_s over the unsealed header encoding and the entropy
+    /// source H_v over Y(H_s), the JAM context strings are built in Rust
+    pub fn verifyBlockSeal(
+        public_key: PublicKey,
+        unsealed_header: []const u8,
+        entropy: [32]u8,
+        mode: SealMode,
+        seal: Signature,
+        entropy_source: Signature,
+    ) SealError!SealOutputs {
+        var outputs: SealOutputs = undefined;
+        const rc = bandersnatch_verify_block_seal(
+            &public_key.bytes,
+            unsealed_header.ptr,
+            unsealed_header.len,
+            &entropy,
+            mode == .ticket,
+            switch (mode) {
+                .ticket => |attempt| attempt,
+                .fallback => 0,
+            },
+            &seal.bytes,
+            &entropy_source.bytes,
+            &outputs.seal,
+            &outputs.entropy_source,
+        );
+        return switch (rc) {
+            0 => outputs,
+            -2, -4 => SealError.SealVerificationFailed,
+            -3, -5 => SealError.EntropySourceVerificationFailed,
+            else => SealError.InvalidSealInput,
+        };
+    }
+
+    /// Verify only the seal H_s, returns Y(H_s)
+    pub fn verifySeal(
+        public_key: PublicKey,
+        unsealed_header: []const u8,
+        entropy: [32]u8,
+        mode: SealMode,
+        seal: Signature,
+    ) SealError![output_length]u8 {
+        var output: [output_length]u8 = undefined;
+        const rc = bandersnatch_verify_seal(
+            &public_key.bytes,
+            unsealed_header.ptr,
+            unsealed_header.len,
+            &entropy,
+            mode == .ticket,
+            switch (mode) {
+                .ticket => |attempt| attempt,
+                .fallback => 0,
+            },
+            &seal.bytes,
+            &output,
+        );
+        return switch (rc) {
+            0 => output,
+            -2, -4 => SealError.SealVerificationFailed,
+            else => SealError.InvalidSealInput,
+        };
+    }
+
+    /// Verify only the entropy source H_v over `seal_output`, Y(H_s), which
+    /// can be read from the seal before it is verified. Returns Y(H_v).
+    pub fn verifyEntropySource(
+        public_key: PublicKey,
+        seal_output: [output_length]u8,
+        entropy_source: Signature,
+    ) SealError![output_length]u8 {
+        var output: [output_length]u8 = undefined;
+        const rc = bandersnatch_verify_entropy_source(
+            &public_key.bytes,
+            &seal_output,
+            &entropy_source.bytes,
+            &output,
+        );
+        return switch (rc) {
+            0 => output,
+            -3, -5 => SealError.EntropySourceVerificationFailed,
+            else => SealError.InvalidSealInput,
+        };
+    }
 };
 
 test "bandersnatch: key pair creation and signing" {
@@ -220,6 +422,24 @@ test "bandersnatch: key pair creation and signing" {
     const output_hash = try sig.outputHash();
     try std.testing.expectEqualSlices(u8, &vrf_output, &output_hash);
 
+    // The output can be evaluated without a signature
+    const unproven_output = try key_pair.vrfOutput(msg);
+    try std.testing.expectEqualSlices(u8, &vrf_output, &unproven_output);
+
+    // A signed block seal verifies with the entropy source in the header
+    var header = [_]u8{1} ** 16 ++ [_]u8{0} ** Bandersnatch.signature_length ++ [_]u8{2} ** 4;
+    const entropy = [_]u8{7} ** 32;
+    const mode: Bandersnatch.SealMode = .{ .ticket = 1 };
+    const block_seal = try key_pair.signBlockSeal(&header, 16, entropy, mode);
+    header[16..][0..Bandersnatch.signature_length].* = block_seal.entropy_source.bytes;
+    const seal_outputs = try Bandersnatch.verifyBlockSeal(key_pair.public_key, &header, entropy, mode, block_seal.seal, block_seal.entropy_source);
+
+    // The seal and entropy source can also be verified one at a time
+    const seal_output = try Bandersnatch.verifySeal(key_pair.public_key, &header, entropy, mode, block_seal.seal);
+    try std.testing.expectEqualSlices(u8, &seal_outputs.seal, &seal_output);
+    const entropy_output = try Bandersnatch.verifyEntropySource(key_pair.public_key, try block_seal.seal.outputHash(), block_seal.entropy_source);
+    try std.testing.expectEqualSlices(u8, &seal_outputs.entropy_source, &entropy_output);
+
     // Test random key generation
     const random_key_pair = try Bandersnatch.KeyPair.generateDeterministic(null);
     const random_sig = try random_key_pair.sign(msg, context);
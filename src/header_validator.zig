@@ -10,12 +10,6 @@ const tracing = @import("tracing");
 const trace = tracing.scoped(.stf);
 const tracy = @import("tracy");
 
-// Constants for seal contexts
-const SEAL_CONTEXT_TICKET = "jam_ticket_seal";
-const SEAL_CONTEXT_FALLBACK = "jam_fallback_seal";
-const ENTROPY_CONTEXT = "jam_entropy";
-const MAX_CONTEXT_BUFFER_SIZE = 64;
-
 /// Errors specific to header validation
 pub const HeaderValidationError = error{
     // Structural validation errors
@@ -78,8 +72,6 @@ pub const SealContext = struct {
     entropy: types.Entropy,
     /// Optional tickets (if in ticket mode)
     tickets: ?[]const types.TicketBody,
-    /// The context prefix (SEAL_CONTEXT_TICKET or SEAL_CONTEXT_FALLBACK)
-    context_prefix: []const u8,
 };
 
 /// Result of header validation
@@ -174,7 +166,6 @@ pub fn HeaderValidator(comptime IOExecutor: type, comptime params: jam_params.Pa
                     .author_key = author_key,
                     .entropy = eta_prime[3],
                     .tickets = tickets.tickets,
-                    .context_prefix = if (tickets.tickets != null) SEAL_CONTEXT_TICKET else SEAL_CONTEXT_FALLBACK,
                 };
 
                 // Spawn seal verification task
@@ -587,73 +578,55 @@ pub fn HeaderValidator(comptime IOExecutor: type, comptime params: jam_params.Pa
             };
             defer self.allocator.free(unsigned_header_bytes);
 
-            // Extract seal signature
-            const seal_signature = crypto.bandersnatch.Bandersnatch.Signature.fromBytes(ctx.header.seal);
-
-            // Build context buffer
-            const context_bytes = blk: {
-                const context_span = span.child(@src(), .seal_build_context);
-                defer context_span.deinit();
-                var context_buf: [MAX_CONTEXT_BUFFER_SIZE]u8 = undefined;
-                var context_len: usize = 0;
-
-                // Add context prefix
-                @memcpy(context_buf[context_len .. context_len + ctx.context_prefix.len], ctx.context_prefix);
-                context_len += ctx.context_prefix.len;
-
-                // Add entropy
-                @memcpy(context_buf[context_len .. context_len + 32], &ctx.entropy);
-                context_len += 32;
-
-                // Handle ticket-specific validation
-                if (ctx.tickets) |ticket_bodies| {
-                    const ticket_span = span.child(@src(), .seal_validate_ticket);
-                    defer ticket_span.deinit();
-                    const slot_in_epoch = ctx.header.slot % params.epoch_length;
-                    const ticket = ticket_bodies[slot_in_epoch];
-
-                    // Verify VRF output matches ticket ID
-                    const seal_vrf_output = seal_signature.outputHash() catch {
-                        span.err("Failed to extract VRF output from seal", .{});
-                        return HeaderValidationError.TicketSealVerificationFailed;
-                    };
+            const Bandersnatch = crypto.bandersnatch.Bandersnatch;
+            const seal_signature = Bandersnatch.Signature.fromBytes(ctx.header.seal);
 
-                    if (!std.mem.eql(u8, &ticket.id, &seal_vrf_output)) {
-                        span.err("Ticket ID mismatch: Ticket ID={s}, VRF output={s}", .{
-                            std.fmt.fmtSliceHexLower(&ticket.id),
-                            std.fmt.fmtSliceHexLower(&seal_vrf_output),
-                        });
-                        return HeaderValidationError.InvalidTicketId;
-                    }
+            // Handle ticket-specific validation
+            const ticket: ?types.TicketBody = if (ctx.tickets) |ticket_bodies|
+                ticket_bodies[ctx.header.slot % params.epoch_length]
+            else
+                null;
+            if (ticket) |t| {
+                const ticket_span = span.child(@src(), .seal_validate_ticket);
+                defer ticket_span.deinit();
+
+                // Verify VRF output matches ticket ID
+                const seal_vrf_output = seal_signature.outputHash() catch {
+                    span.err("Failed to extract VRF output from seal", .{});
+                    return HeaderValidationError.TicketSealVerificationFailed;
+                };
 
-                    // Add ticket attempt to context
-                    context_buf[context_len] = ticket.attempt;
-                    context_len += 1;
+                if (!std.mem.eql(u8, &t.id, &seal_vrf_output)) {
+                    span.err("Ticket ID mismatch: Ticket ID={s}, VRF output={s}", .{
+                        std.fmt.fmtSliceHexLower(&t.id),
+                        std.fmt.fmtSliceHexLower(&seal_vrf_output),
+                    });
+                    return HeaderValidationError.InvalidTicketId;
                 }
+            }
 
-                break :blk context_buf[0..context_len];
-            };
-
-            // Verify signature
+            // Verify signature, the seal context is built by the crypto library
+            const mode: Bandersnatch.SealMode = if (ticket) |t| .{ .ticket = t.attempt } else .fallback;
             {
                 const verify_span = span.child(@src(), .seal_verify_signature);
                 defer verify_span.deinit();
-                const public_key = crypto.bandersnatch.Bandersnatch.PublicKey.fromBytes(ctx.author_key);
-                _ = seal_signature.verify(
-                    context_bytes,
+                _ = Bandersnatch.verifySeal(
+                    Bandersnatch.PublicKey.fromBytes(ctx.author_key),
                     unsigned_header_bytes,
-                    public_key,
+                    ctx.entropy,
+                    mode,
+                    seal_signature,
                 ) catch {
-                    const err = if (ctx.tickets != null)
+                    const err = if (ticket != null)
                         HeaderValidationError.TicketSealVerificationFailed
                     else
                         HeaderValidationError.FallbackSealVerificationFailed;
-                    span.err("{s} seal verification failed", .{ctx.context_prefix});
+                    span.err("{s} seal verification failed", .{@tagName(mode)});
                     return err;
                 };
             }
 
-            span.debug("Seal verification successful (mode: {s})", .{ctx.context_prefix});
+            span.debug("Seal verification successful (mode: {s})", .{@tagName(mode)});
         }
 
         /// Validate VRF entropy source
@@ -679,30 +652,16 @@ pub fn HeaderValidator(comptime IOExecutor: type, comptime params: jam_params.Pa
                 };
             };
 
-            // Build context: ENTROPY_CONTEXT ⌢ Y(Hs)
-            const context_bytes = blk: {
-                var context_buf: [MAX_CONTEXT_BUFFER_SIZE]u8 = undefined;
-                var context_len: usize = 0;
-
-                @memcpy(context_buf[context_len .. context_len + ENTROPY_CONTEXT.len], ENTROPY_CONTEXT);
-                context_len += ENTROPY_CONTEXT.len;
-
-                @memcpy(context_buf[context_len .. context_len + 32], &seal_output_hash);
-                context_len += 32;
-
-                break :blk context_buf[0..context_len];
-            };
-
-            // Verify entropy source signature
+            // Verify entropy source signature, the entropy context
+            // jam_entropy ⌢ Y(Hs) is built by the crypto library
             {
                 const verify_span = span.child(@src(), .entropy_verify_signature);
                 defer verify_span.deinit();
-                const entropy_signature = crypto.bandersnatch.Bandersnatch.Signature.fromBytes(header.entropy_source);
-                const public_key = crypto.bandersnatch.Bandersnatch.PublicKey.fromBytes(author_key);
-                _ = entropy_signature.verify(
-                    context_bytes,
-                    &[_]u8{}, // Empty message for VRF
-                    public_key,
+                const Bandersnatch = crypto.bandersnatch.Bandersnatch;
+                _ = Bandersnatch.verifyEntropySource(
+                    Bandersnatch.PublicKey.fromBytes(author_key),
+                    seal_output_hash,
+                    Bandersnatch.Signature.fromBytes(header.entropy_source),
                 ) catch {
                     span.err("Entropy source verification failed", .{});
                     return HeaderValidationError.EntropySourceVerificationFailed;
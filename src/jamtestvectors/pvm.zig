@@ -19,6 +19,7 @@ pub const Status = enum {
     panic,
     halt,
     @"page-fault",
+    @"out-of-gas",
 };
 
 pub const PVMTestVector = struct {
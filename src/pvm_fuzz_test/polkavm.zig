@@ -126,14 +126,11 @@ pub const TestEnvironment = struct {
         defer executor.deinit();
 
         // Execute a single step, this is the first jump
-        {
-            const execution_result = executor.step();
-            defer execution_result.deinit();
-        }
+        _ = executor.step();
 
         // Now execute the actual instruction
+        // Borrowed from the executor, valid until it is freed
         const execution_result = executor.step();
-        defer execution_result.deinit();
 
         // Capture memory changes if applicable
         var result_memory: ?[]const u8 = null;
@@ -37,6 +37,7 @@ pub const PVMFixture = struct {
         // an invalid instruction was executed)
         halt, // The program terminated normally.
         page_fault, // Program halted
+        out_of_gas, // Gas ran out, only written by vectors recorded from polkavm
     };
 
     pub fn from_vector(allocator: Allocator, vector: *const PVMLib.PVMTestVector) !PVMFixture {
@@ -154,7 +155,9 @@ pub fn runTestFixture(allocator: Allocator, test_vector: *const PVMFixture, path
             .panic => test_vector.expected_status == .panic,
             .page_fault => |addr| test_vector.expected_status == .page_fault and
                 test_vector.expected_page_fault_address == addr,
-            .out_of_gas => test_vector.expected_status == .panic, // NOTE: old vectors panic when out of gas
+            // NOTE: old vectors panic when out of gas
+            .out_of_gas => test_vector.expected_status == .out_of_gas or
+                test_vector.expected_status == .panic,
         },
     };
 
@@ -447,8 +447,7 @@ pub const PVMFuzzer = struct {
 
         // We need to do one step to "initialze" the polkavm
         if (ref_executor) |*executor| {
-            const _r = executor.step();
-            defer _r.deinit();
+            _ = executor.step();
         }
 
         // Main execution loop
@@ -490,15 +489,9 @@ pub const PVMFuzzer = struct {
                     break :cross_check;
                 }
 
-                // Execute one step in reference implementation
+                // Execute one step in reference implementation, the result
+                // borrows from the executor until its next step
                 const ref_result = executor.step();
-                defer ref_result.deinit();
-
-                // we need to inject another step if we run into a hostcall
-                if (current_instruction.instruction == .ecalli) {
-                    const _r = executor.step();
-                    defer _r.deinit();
-                }
 
                 // Compare states
                 try compareRegisters("Step", exec_ctx.registers[0..13], ref_result.getRegisters()[0..13]);
@@ -516,6 +509,12 @@ pub const PVMFuzzer = struct {
                     std.debug.print("  Reference impl: {any}\n", .{ref_result.raw.status});
                     return error.CrossCheckStatusMismatch;
                 }
+
+                // we need to inject another step if we run into a hostcall,
+                // only after comparing as it invalidates ref_result
+                if (current_instruction.instruction == .ecalli) {
+                    _ = executor.step();
+                }
             }
 
             // Process step result
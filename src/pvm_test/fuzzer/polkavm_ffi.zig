@@ -23,6 +23,112 @@ pub const ExecutionStatus = enum(c_int) {
     Segfault = 3,
     InstanceRunError = 4,
     Running = 5,
+    /// Stopped by an interrupt or a run limit, execution can be continued
+    Interrupted = 6,
+    /// Stopped on an `ecalli`, execution continues after it on the next step
+    HostCall = 7,
+};
+
+pub const PageFaultAction = enum(c_int) {
+    Final = 0,
+    MapZeroPage = 1,
+    MapPage = 2,
+};
+
+pub const PageAccess = enum(c_int) {
+    ReadOnly = 0,
+    ReadWrite = 1,
+};
+
+/// Record of the instruction executed by a single step
+pub const StepTrace = extern struct {
+    /// False when the step did not execute an instruction, e.g. the first
+    /// step which only enters the program
+    executed: bool,
+    opcode: u8,
+    pc_before: u32,
+    pc_after: u32,
+    gas_charged: i64,
+    has_memory_access: bool,
+    memory_is_write: bool,
+    memory_address: u32,
+    memory_size: u32,
+    /// Registers named by the instruction, in operand order
+    registers: [3]u8,
+    register_count: u8,
+    /// Immediates and branch targets of the instruction, in operand order
+    immediates: [2]u64,
+    immediate_count: u8,
+    /// Disassembly of the instruction, truncated to fit
+    text: [64]u8,
+    text_len: usize,
+
+    pub fn getText(self: *const StepTrace) []const u8 {
+        return self.text[0..self.text_len];
+    }
+
+    pub fn getRegisters(self: *const StepTrace) []const u8 {
+        return self.registers[0..self.register_count];
+    }
+
+    pub fn getImmediates(self: *const StepTrace) []const u64 {
+        return self.immediates[0..self.immediate_count];
+    }
+
+    pub fn format(
+        self: StepTrace,
+        comptime _: []const u8,
+        _: std.fmt.FormatOptions,
+        writer: anytype,
+    ) !void {
+        if (!self.executed) return writer.writeAll("<no instruction executed>");
+        try writer.print("{d:0>4}: {s} -> {d:0>4} gas={d}", .{
+            self.pc_before,
+            self.text[0..self.text_len],
+            self.pc_after,
+            self.gas_charged,
+        });
+        if (self.has_memory_access) {
+            try writer.print(" {s} 0x{x:0>8}[{d}]", .{
+                if (self.memory_is_write) "write" else "read",
+                self.memory_address,
+                self.memory_size,
+            });
+        }
+    }
+};
+
+/// Format of a program passed across the FFI
+pub const ProgramFormat = enum(c_int) {
+    polkavm = 0,
+    jam = 1,
+};
+
+pub const EdgeCase = enum(c_int) {
+    division_by_zero = 0,
+    division_overflow = 1,
+    shift_overflow = 2,
+    sign_extension = 3,
+};
+
+pub const PcCount = extern struct {
+    pc: u32,
+    count: u64,
+};
+
+/// Gas polkavm charges when entering the basic block at `start_pc`
+pub const BlockGasCost = extern struct {
+    start_pc: u32,
+    cost: i64,
+};
+
+/// Contiguous mapped pages with the same access
+pub const MappedRange = extern struct {
+    address: u32,
+    length: u32,
+    is_writable: bool,
+    /// Mapped while resolving a page fault rather than at creation
+    is_dynamic: bool,
 };
 
 const RawExecutionResult = extern struct {
@@ -33,19 +139,23 @@ const RawExecutionResult = extern struct {
     registers: [13]u64,
     gas_remaining: i64,
     segfault_address: u32,
+    trace: StepTrace,
 };
 
+/// Result of a single step. The pages are borrowed from the executor and
+/// are only valid until the next step or until the executor is freed.
 pub const ExecutionResult = struct {
     raw: RawExecutionResult,
 
-    pub fn deinit(self: *const ExecutionResult) void {
-        free_execution_result(self.raw);
-    }
-
     pub fn getPages(self: *const ExecutionResult) []const MemoryPage {
+        if (self.raw.page_count == 0) return &.{};
         return self.raw.pages.?[0..self.raw.page_count];
     }
 
+    pub fn getTrace(self: *const ExecutionResult) *const StepTrace {
+        return &self.raw.trace;
+    }
+
     pub fn getRegisters(self: *const ExecutionResult) []const u64 {
         return &self.raw.registers;
     }
@@ -53,7 +163,7 @@ pub const ExecutionResult = struct {
     pub fn isFinished(self: *const ExecutionResult) bool {
         return switch (self.raw.status) {
             .Success, .Trap, .OutOfGas, .Segfault, .InstanceRunError => true,
-            .Running => false,
+            .Running, .Interrupted, .HostCall => false,
         };
     }
 };
@@ -61,8 +171,214 @@ pub const ExecutionResult = struct {
 // Opaque type for the executor
 const ProgramExecutor = opaque {};
 
+/// Interrupts an executor from another thread, outlives the executor
+pub const InterruptHandle = opaque {
+    /// Stops the current run of the executor before its next step, or the
+    /// next run before its first step if none is in progress
+    pub fn interrupt(self: *const InterruptHandle) void {
+        interrupt_executor(self);
+    }
+
+    pub fn deinit(self: *InterruptHandle) void {
+        free_interrupt_handle(self);
+    }
+};
+
+/// Scalar fields of a service account in the reference host model
+pub const ServiceInfo = extern struct {
+    code_hash: [32]u8 = [_]u8{0} ** 32,
+    balance: u64 = 0,
+    min_item_gas: u64 = 0,
+    min_memo_gas: u64 = 0,
+    storage_offset: u64 = 0,
+    creation_slot: u32 = 0,
+    last_accumulation_slot: u32 = 0,
+    parent_service: u32 = 0,
+    footprint_items: u32 = 0,
+    footprint_bytes: u64 = 0,
+};
+
+/// In-memory service accounts the reference general host calls (gas,
+/// fetch, lookup, read, write, info, log) run against
+pub const HostEnvironment = opaque {
+    pub fn init(service_id: u32) *HostEnvironment {
+        return create_host_environment(service_id);
+    }
+
+    pub fn deinit(self: *HostEnvironment) void {
+        free_host_environment(self);
+    }
+
+    pub fn setService(self: *HostEnvironment, service_id: u32, info: ServiceInfo) void {
+        host_env_set_service(self, service_id, &info);
+    }
+
+    pub fn getService(self: *const HostEnvironment, service_id: u32) ?ServiceInfo {
+        var info: ServiceInfo = undefined;
+        return if (host_env_get_service(self, service_id, &info)) info else null;
+    }
+
+    /// Seeds storage without touching the footprint, the service must exist
+    pub fn setStorage(self: *HostEnvironment, service_id: u32, key: []const u8, value: []const u8) !void {
+        if (!host_env_set_storage(self, service_id, key.ptr, key.len, value.ptr, value.len))
+            return error.UnknownService;
+    }
+
+    /// Returns the value owned by the caller, null if the key is not set
+    pub fn getStorage(self: *const HostEnvironment, allocator: std.mem.Allocator, service_id: u32, key: []const u8) !?[]u8 {
+        const len = host_env_get_storage(self, service_id, key.ptr, key.len, null, 0);
+        if (len < 0) return null;
+        const value = try allocator.alloc(u8, @intCast(len));
+        _ = host_env_get_storage(self, service_id, key.ptr, key.len, value.ptr, value.len);
+        return value;
+    }
+
+    pub fn storageCount(self: *const HostEnvironment, service_id: u32) usize {
+        return host_env_storage_count(self, service_id);
+    }
+
+    pub fn setPreimage(self: *HostEnvironment, service_id: u32, hash: [32]u8, data: []const u8) !void {
+        if (!host_env_set_preimage(self, service_id, &hash, data.ptr, data.len))
+            return error.UnknownService;
+    }
+
+    pub fn setFetch(self: *HostEnvironment, selector: u64, index_1: u64, index_2: u64, data: []const u8) void {
+        host_env_set_fetch(self, selector, index_1, index_2, data.ptr, data.len);
+    }
+
+    pub fn logCount(self: *const HostEnvironment) usize {
+        return host_env_log_count(self);
+    }
+};
+
+pub const HostCallOutcome = enum(c_int) {
+    @"continue" = 0,
+    panic = 1,
+    out_of_gas = 2,
+    /// Cut short by the run limits while an inner machine ran, never
+    /// recorded in a log
+    interrupted = 3,
+};
+
+/// Host calls with the registers, gas and memory writes the host applied,
+/// recorded from an executor or built from a run of our own PVM
+pub const HostCallLog = opaque {
+    pub fn init() *HostCallLog {
+        return create_host_call_log();
+    }
+
+    pub fn deinit(self: *HostCallLog) void {
+        free_host_call_log(self);
+    }
+
+    /// Bit i of `written_registers` is set if the host changed register i.
+    /// Only finished host calls can be logged, not `interrupted` ones.
+    pub fn push(self: *HostCallLog, index: u32, outcome: HostCallOutcome, gas: i64, registers: *const [13]u64, written_registers: u16) !void {
+        if (!host_call_log_push(self, index, @intCast(@intFromEnum(outcome)), gas, registers, written_registers))
+            return error.InvalidOutcome;
+    }
+
+    /// Adds a memory write to the last pushed host call
+    pub fn pushWrite(self: *HostCallLog, address: u32, data: []const u8) !void {
+        if (!host_call_log_push_write(self, address, data.ptr, data.len))
+            return error.EmptyLog;
+    }
+
+    /// Caller owns the returned bytes
+    pub fn encode(self: *const HostCallLog, allocator: std.mem.Allocator) ![]u8 {
+        const len = host_call_log_encode(self, null, 0);
+        const bytes = try allocator.alloc(u8, len);
+        _ = host_call_log_encode(self, bytes.ptr, bytes.len);
+        return bytes;
+    }
+
+    pub fn decode(bytes: []const u8) !*HostCallLog {
+        return host_call_log_decode(bytes.ptr, bytes.len) orelse error.InvalidLog;
+    }
+};
+
+/// Opaque snapshot of an executor's full state
+pub const ExecutorSnapshot = opaque {};
+
+/// Inputs of one job of an `ExecutorBatch`, the slices must outlive the run
+pub const BatchJob = extern struct {
+    bytecode: [*]const u8,
+    bytecode_len: usize,
+    initial_pages: [*]const MemoryPage,
+    page_count: usize,
+    initial_registers: [*]const u64,
+    gas_limit: u64,
+    /// Run limits as in `ProgramExecutor.setRunLimits`, 0 for unlimited
+    max_steps: u64 = 0,
+    max_millis: u64 = 0,
+
+    pub fn init(bytecode: []const u8, pages: []const MemoryPage, registers: *const [13]u64, gas_limit: u64) BatchJob {
+        return .{
+            .bytecode = bytecode.ptr,
+            .bytecode_len = bytecode.len,
+            .initial_pages = pages.ptr,
+            .page_count = pages.len,
+            .initial_registers = registers,
+            .gas_limit = gas_limit,
+        };
+    }
+
+    /// Stops the job after `max_steps` steps or `max_millis` milliseconds,
+    /// leaving it `Interrupted`
+    pub fn withRunLimits(self: BatchJob, max_steps: u64, max_millis: u64) BatchJob {
+        var job = self;
+        job.max_steps = max_steps;
+        job.max_millis = max_millis;
+        return job;
+    }
+};
+
+const RawExecutorBatch = opaque {};
+
+/// Jobs run to completion on a pool of worker threads. The results borrow
+/// their pages from the batch and are valid until it is freed.
+pub const ExecutorBatch = struct {
+    batch: *RawExecutorBatch,
+    results: []RawExecutionResult,
+    errors: []c_int,
+    allocator: std.mem.Allocator,
+
+    /// Runs on all available cores when `threads` is 0
+    pub fn run(allocator: std.mem.Allocator, jobs: []const BatchJob, threads: usize) !ExecutorBatch {
+        const results = try allocator.alloc(RawExecutionResult, jobs.len);
+        errdefer allocator.free(results);
+        const errors = try allocator.alloc(c_int, jobs.len);
+
+        return .{
+            .batch = run_executor_batch(jobs.ptr, jobs.len, threads, results.ptr, errors.ptr),
+            .results = results,
+            .errors = errors,
+            .allocator = allocator,
+        };
+    }
+
+    pub fn deinit(self: *ExecutorBatch) void {
+        free_executor_batch(self.batch);
+        self.allocator.free(self.results);
+        self.allocator.free(self.errors);
+    }
+
+    /// Final result of job `index`, null if its executor could not be created
+    /// or the job panicked
+    pub fn result(self: *const ExecutorBatch, index: usize) ?ExecutionResult {
+        if (self.errors[index] != 0) return null;
+        return .{ .raw = self.results[index] };
+    }
+};
+
 extern "c" fn init_logging() void;
-extern "c" fn free_execution_result(result: RawExecutionResult) void;
+extern "c" fn clear_module_cache() void;
+
+extern "c" fn coverage_set_enabled(enabled: bool) void;
+extern "c" fn coverage_reset() void;
+extern "c" fn coverage_opcode_counts(out: ?[*]u64, capacity: usize) usize;
+extern "c" fn coverage_edge_case_counts(out: ?[*]u64, capacity: usize) usize;
+extern "c" fn coverage_pc_counts(out: ?[*]PcCount, capacity: usize) usize;
 
 extern "c" fn create_executor(
     bytecode: [*]const u8,
@@ -73,22 +389,167 @@ extern "c" fn create_executor(
     gas_limit: u64,
 ) ?*ProgramExecutor;
 
+extern "c" fn create_executor_from_standard_program(
+    program: [*]const u8,
+    program_len: usize,
+    args: ?[*]const u8,
+    args_len: usize,
+    gas_limit: u64,
+) ?*ProgramExecutor;
+
 extern "c" fn step_executor(
     executor: *ProgramExecutor,
 ) RawExecutionResult;
 
+extern "c" fn executor_set_breakpoint(executor: *ProgramExecutor, pc: u32) void;
+extern "c" fn executor_clear_breakpoint(executor: *ProgramExecutor, pc: u32) bool;
+extern "c" fn executor_clear_breakpoints(executor: *ProgramExecutor) void;
+extern "c" fn executor_run_until_breakpoint(executor: *ProgramExecutor) RawExecutionResult;
+extern "c" fn executor_run_until_pc(executor: *ProgramExecutor, pc: u32) RawExecutionResult;
+
+extern "c" fn executor_set_run_limits(executor: *ProgramExecutor, max_steps: u64, max_millis: u64) void;
+extern "c" fn executor_run(executor: *ProgramExecutor) RawExecutionResult;
+extern "c" fn executor_interrupt_handle(executor: *const ProgramExecutor) *InterruptHandle;
+extern "c" fn interrupt_executor(handle: *const InterruptHandle) void;
+extern "c" fn free_interrupt_handle(handle: *InterruptHandle) void;
+
+extern "c" fn create_host_environment(service_id: u32) *HostEnvironment;
+extern "c" fn free_host_environment(env: *HostEnvironment) void;
+extern "c" fn host_env_set_service(env: *HostEnvironment, service_id: u32, info: *const ServiceInfo) void;
+extern "c" fn host_env_get_service(env: *const HostEnvironment, service_id: u32, info_out: *ServiceInfo) bool;
+extern "c" fn host_env_set_storage(env: *HostEnvironment, service_id: u32, key: [*]const u8, key_len: usize, value: [*]const u8, value_len: usize) bool;
+extern "c" fn host_env_get_storage(env: *const HostEnvironment, service_id: u32, key: [*]const u8, key_len: usize, out: ?[*]u8, capacity: usize) isize;
+extern "c" fn host_env_storage_count(env: *const HostEnvironment, service_id: u32) usize;
+extern "c" fn host_env_set_preimage(env: *HostEnvironment, service_id: u32, hash: *const [32]u8, data: [*]const u8, len: usize) bool;
+extern "c" fn host_env_set_fetch(env: *HostEnvironment, selector: u64, index_1: u64, index_2: u64, data: [*]const u8, len: usize) void;
+extern "c" fn host_env_log_count(env: *const HostEnvironment) usize;
+extern "c" fn executor_handle_host_call(executor: *ProgramExecutor, env: *HostEnvironment) bool;
+extern "c" fn executor_run_with_host(executor: *ProgramExecutor, env: *HostEnvironment) RawExecutionResult;
+
+extern "c" fn executor_start_recording(executor: *ProgramExecutor) void;
+extern "c" fn executor_take_recording(executor: *ProgramExecutor) ?*HostCallLog;
+extern "c" fn executor_run_replay(executor: *ProgramExecutor, log: *const HostCallLog, position: *usize, result_out: *RawExecutionResult) c_int;
+extern "c" fn create_host_call_log() *HostCallLog;
+extern "c" fn host_call_log_push(log: *HostCallLog, index: u32, outcome: u8, gas: i64, registers: *const [13]u64, written_registers: u16) bool;
+extern "c" fn host_call_log_push_write(log: *HostCallLog, address: u32, data: [*]const u8, len: usize) bool;
+extern "c" fn host_call_log_encode(log: *const HostCallLog, out: ?[*]u8, capacity: usize) usize;
+extern "c" fn host_call_log_decode(bytes: [*]const u8, len: usize) ?*HostCallLog;
+extern "c" fn free_host_call_log(log: *HostCallLog) void;
+
 extern "c" fn is_executor_finished(
     executor: *const ProgramExecutor,
 ) bool;
 
+extern "c" fn resolve_page_fault(
+    executor: *ProgramExecutor,
+    action: PageFaultAction,
+    access: PageAccess,
+    data: ?[*]const u8,
+    data_len: usize,
+) c_int;
+
+extern "c" fn snapshot_executor(
+    executor: *const ProgramExecutor,
+) ?*ExecutorSnapshot;
+
+extern "c" fn restore_executor(
+    executor: *ProgramExecutor,
+    snapshot: *const ExecutorSnapshot,
+) bool;
+
+extern "c" fn free_executor_snapshot(
+    snapshot: *ExecutorSnapshot,
+) void;
+
+extern "c" fn executor_get_register(executor: *const ProgramExecutor, index: u32, value_out: *u64) c_int;
+extern "c" fn executor_set_register(executor: *ProgramExecutor, index: u32, value: u64) c_int;
+extern "c" fn executor_set_next_pc(executor: *ProgramExecutor, pc: u32) void;
+extern "c" fn executor_set_entry_pc(executor: *ProgramExecutor, pc: u32) c_int;
+extern "c" fn executor_set_entry_export(executor: *ProgramExecutor, name: [*]const u8, name_len: usize) c_int;
+extern "c" fn executor_get_gas(executor: *const ProgramExecutor) i64;
+extern "c" fn executor_set_gas(executor: *ProgramExecutor, gas: i64) void;
+extern "c" fn executor_read_memory(executor: *const ProgramExecutor, address: u32, buffer: [*]u8, len: usize) c_int;
+extern "c" fn executor_write_memory(executor: *ProgramExecutor, address: u32, data: [*]const u8, len: usize) c_int;
+
+extern "c" fn executor_block_gas_costs(
+    executor: *const ProgramExecutor,
+    costs_out: ?[*]BlockGasCost,
+    capacity: usize,
+) usize;
+
+extern "c" fn executor_memory_map(
+    executor: *const ProgramExecutor,
+    ranges_out: ?[*]MappedRange,
+    capacity: usize,
+) usize;
+
+extern "c" fn disassemble_program(
+    program: [*]const u8,
+    program_len: usize,
+    format: ProgramFormat,
+    out: ?[*]u8,
+    capacity: usize,
+) isize;
+
 extern "c" fn free_executor(
     executor: *ProgramExecutor,
 ) void;
 
+extern "c" fn run_executor_batch(jobs: [*]const BatchJob, job_count: usize, threads: usize, results_out: [*]RawExecutionResult, errors_out: [*]c_int) *RawExecutorBatch;
+extern "c" fn free_executor_batch(batch: *RawExecutorBatch) void;
+
+extern "c" fn record_test_vector(
+    name: [*:0]const u8,
+    program: [*]const u8,
+    program_len: usize,
+    initial_pages: [*]const MemoryPage,
+    page_count: usize,
+    initial_registers: [*]const u64,
+    gas_limit: i64,
+    output_path: [*:0]const u8,
+) c_int;
+
 pub fn initLogging() void {
     init_logging();
 }
 
+/// Executors share compiled modules keyed by blob content, this drops them
+pub fn clearModuleCache() void {
+    clear_module_cache();
+}
+
+/// Coverage is collected globally across executors once enabled
+pub fn setCoverageEnabled(enabled: bool) void {
+    coverage_set_enabled(enabled);
+}
+
+pub fn resetCoverage() void {
+    coverage_reset();
+}
+
+/// Execution counts indexed by opcode
+pub fn opcodeCoverage() [256]u64 {
+    var counts: [256]u64 = undefined;
+    _ = coverage_opcode_counts(&counts, counts.len);
+    return counts;
+}
+
+/// Counts indexed by `EdgeCase`
+pub fn edgeCaseCoverage() std.EnumArray(EdgeCase, u64) {
+    var counts = std.EnumArray(EdgeCase, u64).initFill(0);
+    _ = coverage_edge_case_counts(&counts.values, counts.values.len);
+    return counts;
+}
+
+/// Execution counts of every reached PC, caller owns the slice. Executors
+/// add their PCs when they are freed.
+pub fn pcCoverage(allocator: std.mem.Allocator) ![]PcCount {
+    const count = coverage_pc_counts(null, 0);
+    const counts = try allocator.alloc(PcCount, count);
+    _ = coverage_pc_counts(counts.ptr, counts.len);
+    return counts;
+}
+
 /// Wrapper for the ProgramExecutor that provides a more Zig-friendly interface
 pub const Executor = struct {
     executor: *ProgramExecutor,
@@ -98,8 +559,31 @@ pub const Executor = struct {
     pub const Error = error{
         ExecutorCreationFailed,
         ExecutionError,
+        NoPendingFault,
+        PageDataTooLarge,
+        MemoryError,
+        SnapshotFailed,
+        RestoreFailed,
+        InvalidRegister,
+        InaccessibleMemory,
+        InvalidEntryPoint,
+        UnknownExport,
+        AlreadyStarted,
+        ReplayLogExhausted,
+        ReplayHostCallMismatch,
+        ReplayLogNotExhausted,
+        ReplayRegisterMismatch,
     };
 
+    fn stateError(code: c_int) Error!void {
+        return switch (code) {
+            0 => {},
+            1 => error.InvalidRegister,
+            2 => error.InaccessibleMemory,
+            else => error.MemoryError,
+        };
+    }
+
     pub fn init(
         bytecode: []const u8,
         pages: []const MemoryPage,
@@ -120,6 +604,26 @@ pub const Executor = struct {
         };
     }
 
+    /// Creates an executor from a standard program blob (without metadata),
+    /// laying out memory and registers according to the Y function.
+    pub fn initStandardProgram(
+        program: []const u8,
+        args: []const u8,
+        gas_limit: u64,
+    ) Error!Self {
+        const executor = create_executor_from_standard_program(
+            program.ptr,
+            program.len,
+            args.ptr,
+            args.len,
+            gas_limit,
+        ) orelse return error.ExecutorCreationFailed;
+
+        return Self{
+            .executor = executor,
+        };
+    }
+
     pub fn deinit(self: *Self) void {
         free_executor(self.executor);
     }
@@ -130,19 +634,245 @@ pub const Executor = struct {
         };
     }
 
+    pub fn setBreakpoint(self: *Self, pc: u32) void {
+        executor_set_breakpoint(self.executor, pc);
+    }
+
+    /// Returns false if no breakpoint was set at `pc`
+    pub fn clearBreakpoint(self: *Self, pc: u32) bool {
+        return executor_clear_breakpoint(self.executor, pc);
+    }
+
+    pub fn clearBreakpoints(self: *Self) void {
+        executor_clear_breakpoints(self.executor);
+    }
+
+    /// Runs until a breakpoint is hit (status `running`), a fault happens
+    /// or the program finishes
+    pub fn runUntilBreakpoint(self: *Self) ExecutionResult {
+        return .{
+            .raw = executor_run_until_breakpoint(self.executor),
+        };
+    }
+
+    /// Runs until the instruction at `pc` is next, ignoring breakpoints
+    pub fn runUntilPc(self: *Self, pc: u32) ExecutionResult {
+        return .{
+            .raw = executor_run_until_pc(self.executor, pc),
+        };
+    }
+
     pub fn isFinished(self: *const Self) bool {
         return is_executor_finished(self.executor);
     }
 
+    /// Resolves the page fault the executor stopped on. Mapping a page
+    /// resumes execution at the faulting instruction on the next step.
+    pub fn resolvePageFault(
+        self: *Self,
+        action: PageFaultAction,
+        access: PageAccess,
+        data: []const u8,
+    ) Error!void {
+        return switch (resolve_page_fault(self.executor, action, access, data.ptr, data.len)) {
+            0 => {},
+            1 => error.NoPendingFault,
+            2 => error.PageDataTooLarge,
+            else => error.MemoryError,
+        };
+    }
+
+    /// Captures registers, PC, gas and all mapped memory, fails while the
+    /// executor has inner machines. The snapshot is owned by the caller and
+    /// must be released with `freeSnapshot`.
+    pub fn snapshot(self: *const Self) Error!*ExecutorSnapshot {
+        return snapshot_executor(self.executor) orelse error.SnapshotFailed;
+    }
+
+    /// Rolls the executor back to `snapshot`, which stays valid. Fails while
+    /// recording host calls.
+    pub fn restore(self: *Self, snapshot: *const ExecutorSnapshot) Error!void {
+        if (!restore_executor(self.executor, snapshot)) return error.RestoreFailed;
+    }
+
+    pub fn freeSnapshot(snapshot: *ExecutorSnapshot) void {
+        free_executor_snapshot(snapshot);
+    }
+
+    pub fn getRegister(self: *const Self, index: u32) Error!u64 {
+        var value: u64 = undefined;
+        try stateError(executor_get_register(self.executor, index, &value));
+        return value;
+    }
+
+    pub fn setRegister(self: *Self, index: u32, value: u64) Error!void {
+        return stateError(executor_set_register(self.executor, index, value));
+    }
+
+    /// Sets the program counter the next step starts from
+    pub fn setNextPc(self: *Self, pc: u32) void {
+        executor_set_next_pc(self.executor, pc);
+    }
+
+    /// Starts execution at `pc`, which must start a basic block. Fails with
+    /// `AlreadyStarted` after the first step.
+    pub fn setEntryPc(self: *Self, pc: u32) Error!void {
+        return entryPointError(executor_set_entry_pc(self.executor, pc));
+    }
+
+    /// Starts execution at the named export. Fails with `AlreadyStarted`
+    /// after the first step.
+    pub fn setEntryExport(self: *Self, name: []const u8) Error!void {
+        return entryPointError(executor_set_entry_export(self.executor, name.ptr, name.len));
+    }
+
+    fn entryPointError(code: c_int) Error!void {
+        return switch (code) {
+            0 => {},
+            1 => error.InvalidEntryPoint,
+            2 => error.UnknownExport,
+            else => error.AlreadyStarted,
+        };
+    }
+
+    pub fn getGas(self: *const Self) i64 {
+        return executor_get_gas(self.executor);
+    }
+
+    pub fn setGas(self: *Self, gas: i64) void {
+        executor_set_gas(self.executor, gas);
+    }
+
+    /// Reads `buffer.len` bytes at `address`, all pages must be mapped
+    pub fn readMemory(self: *const Self, address: u32, buffer: []u8) Error!void {
+        return stateError(executor_read_memory(self.executor, address, buffer.ptr, buffer.len));
+    }
+
+    /// Writes `data` at `address`, all pages must be mapped writable
+    pub fn writeMemory(self: *Self, address: u32, data: []const u8) Error!void {
+        return stateError(executor_write_memory(self.executor, address, data.ptr, data.len));
+    }
+
+    /// Returns the gas charged on entry of every basic block, caller owns
+    /// the returned slice
+    pub fn blockGasCosts(self: *const Self, allocator: std.mem.Allocator) ![]BlockGasCost {
+        const count = executor_block_gas_costs(self.executor, null, 0);
+        const costs = try allocator.alloc(BlockGasCost, count);
+        _ = executor_block_gas_costs(self.executor, costs.ptr, costs.len);
+        return costs;
+    }
+
+    /// Mapped page ranges in address order, caller owns the slice
+    pub fn memoryMap(self: *const Self, allocator: std.mem.Allocator) ![]MappedRange {
+        const count = executor_memory_map(self.executor, null, 0);
+        const ranges = try allocator.alloc(MappedRange, count);
+        _ = executor_memory_map(self.executor, ranges.ptr, ranges.len);
+        return ranges;
+    }
+
+    /// Caps the steps and wall-clock time of every run, 0 means unlimited.
+    /// A run stopped by a limit ends with status `Interrupted`.
+    pub fn setRunLimits(self: *Self, max_steps: u64, max_millis: u64) void {
+        executor_set_run_limits(self.executor, max_steps, max_millis);
+    }
+
+    /// Returns a handle to interrupt runs from another thread, free it
+    /// with `deinit`
+    pub fn interruptHandle(self: *const Self) *InterruptHandle {
+        return executor_interrupt_handle(self.executor);
+    }
+
+    /// Runs the host call the executor is stopped on (status `HostCall`),
+    /// returns false if it is not stopped on one
+    pub fn handleHostCall(self: *Self, env: *HostEnvironment) bool {
+        return executor_handle_host_call(self.executor, env);
+    }
+
+    /// Runs until the program finishes or a run limit is reached, serving
+    /// host calls from `env`
+    pub fn runWithHost(self: *Self, env: *HostEnvironment) ExecutionResult {
+        return .{
+            .raw = executor_run_with_host(self.executor, env),
+        };
+    }
+
+    /// Records every host call from now on, including the effects applied
+    /// through `setRegister`, `writeMemory` and `setGas`
+    pub fn startRecording(self: *Self) void {
+        executor_start_recording(self.executor);
+    }
+
+    /// Returns the recorded log, free it with `deinit`
+    pub fn takeRecording(self: *Self) ?*HostCallLog {
+        return executor_take_recording(self.executor);
+    }
+
+    /// Runs answering host calls from `log` instead of a host. `position`
+    /// is the index of the next record, start at 0 and pass it again to
+    /// continue a run stopped by a limit.
+    pub fn runReplay(self: *Self, log: *const HostCallLog, position: *usize) Error!ExecutionResult {
+        var result: ExecutionResult = undefined;
+        return switch (executor_run_replay(self.executor, log, position, &result.raw)) {
+            0 => result,
+            1 => error.ReplayLogExhausted,
+            2 => error.ReplayHostCallMismatch,
+            4 => error.ReplayLogNotExhausted,
+            5 => error.ReplayRegisterMismatch,
+            else => error.MemoryError,
+        };
+    }
+
+    /// Runs until the program finishes or a run limit is reached
     pub fn runToCompletion(self: *Self) Error!ExecutionResult {
-        var last_result: ExecutionResult = undefined;
-        while (!self.isFinished()) {
-            last_result = self.step();
-        }
-        return last_result;
+        return .{
+            .raw = executor_run(self.executor),
+        };
     }
 };
 
+/// Runs a JAM program (deblob format) to completion on polkavm and writes
+/// the run as a w3f PVM test vector JSON file, usable by `src/pvm_test.zig`.
+/// Needs polkavm_ffi built with its `test-vectors` feature.
+pub fn recordTestVector(
+    name: [:0]const u8,
+    program: []const u8,
+    pages: []const MemoryPage,
+    registers: *const [13]u64,
+    gas_limit: i64,
+    output_path: [:0]const u8,
+) !void {
+    return switch (record_test_vector(
+        name.ptr,
+        program.ptr,
+        program.len,
+        pages.ptr,
+        pages.len,
+        registers,
+        gas_limit,
+        output_path.ptr,
+    )) {
+        0 => {},
+        1 => error.InvalidArgument,
+        2 => error.ExecutorCreationFailed,
+        3 => error.ExecutionError,
+        4 => error.SerializationError,
+        else => error.IoError,
+    };
+}
+
+/// Disassembles a program with the reference decoder, caller owns the text
+pub fn disassembleProgram(
+    allocator: std.mem.Allocator,
+    program: []const u8,
+    format: ProgramFormat,
+) ![]u8 {
+    const len = disassemble_program(program.ptr, program.len, format, null, 0);
+    if (len < 0) return error.InvalidProgram;
+    const text = try allocator.alloc(u8, @intCast(len));
+    _ = disassemble_program(program.ptr, program.len, format, text.ptr, text.len);
+    return text;
+}
+
 /// Convenience function to build program bytes from a GeneratedProgram
 pub fn buildProgramBytes(
     allocator: std.mem.Allocator,
@@ -221,8 +951,7 @@ test "stepped execution" {
                 // Test step-by-step execution
                 var step_count: usize = 0;
                 while (!executor.isFinished()) {
-                    const result = executor.step();
-                    defer result.deinit();
+                    _ = executor.step();
                     // try std.testing.expect(result.raw.status != .Running);
                     step_count += 1;
                 }
@@ -243,7 +972,6 @@ test "stepped execution" {
                 defer executor.deinit();
 
                 const result = try executor.runToCompletion();
-                defer result.deinit();
 
                 try std.testing.expect(result.isFinished());
             }
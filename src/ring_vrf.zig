@@ -28,6 +28,14 @@ extern fn new_ring_vrf_prover(
     prover_idx: usize,
 ) ?*Prover;
 
+extern fn new_ring_vrf_prover_with_padding_report(
+    secret: [*]const u8,
+    public_keys: [*]const u8,
+    public_keys_len: usize,
+    prover_idx: usize,
+    padded_out: [*]bool,
+) ?*Prover;
+
 extern fn vrf_sign(
     prover: *const Prover,
     vrf_input_data: [*]const u8,
@@ -68,6 +76,26 @@ pub const RingProver = struct {
         return RingProver{ .ptr = ptr };
     }
 
+    /// Like `init`, additionally sets `padded[i]` when key `i` could not be
+    /// decoded and was replaced with the padding point
+    pub fn initReportPadding(
+        secret: types.BandersnatchPublic,
+        public_keys: []const types.BandersnatchPublic,
+        prover_idx: usize,
+        padded: []bool,
+    ) Error!RingProver {
+        std.debug.assert(padded.len == public_keys.len);
+        const ptr = new_ring_vrf_prover_with_padding_report(
+            @ptrCast(&secret),
+            @ptrCast(public_keys.ptr),
+            public_keys.len * @sizeOf(types.BandersnatchPublic),
+            prover_idx,
+            padded.ptr,
+        ) orelse return Error.ProverCreationFailed;
+
+        return RingProver{ .ptr = ptr };
+    }
+
     pub fn deinit(self: *RingProver) void {
         free_ring_vrf_prover(self.ptr);
         self.* = undefined;
@@ -139,6 +167,12 @@ extern fn new_ring_vrf_verifier(
     public_keys_len: usize,
 ) ?*Verifier;
 
+extern fn new_ring_vrf_verifier_with_padding_report(
+    public_keys: [*]const u8,
+    public_keys_len: usize,
+    padded_out: [*]bool,
+) ?*Verifier;
+
 extern fn vrf_verify(
     verifier: *const Verifier,
     vrf_input_data: [*]const u8,
@@ -182,6 +216,19 @@ pub const RingVerifier = struct {
         return RingVerifier{ .ptr = ptr };
     }
 
+    /// Like `init`, additionally sets `padded[i]` when key `i` could not be
+    /// decoded and was replaced with the padding point
+    pub fn initReportPadding(public_keys: []const types.BandersnatchPublic, padded: []bool) Error!RingVerifier {
+        std.debug.assert(padded.len == public_keys.len);
+        const ptr = new_ring_vrf_verifier_with_padding_report(
+            @ptrCast(public_keys.ptr),
+            public_keys.len * @sizeOf(types.BandersnatchPublic),
+            padded.ptr,
+        ) orelse return Error.VerifierCreationFailed;
+
+        return RingVerifier{ .ptr = ptr };
+    }
+
     pub fn deinit(self: *RingVerifier) void {
         free_ring_vrf_verifier(self.ptr);
         self.* = undefined;
@@ -332,6 +379,20 @@ test "ring_vrf.padding: get padding point" {
     std.debug.print("Padding point for ring size {d}: {any}\n", .{ ring_size, std.fmt.fmtSliceHexLower(&padding_point) });
 }
 
+/// Validity of a Bandersnatch public key, ring construction accepts any key
+/// on the curve
+pub const KeyValidity = enum(c_int) {
+    invalid = 0,
+    on_curve = 1,
+    in_prime_subgroup = 2,
+};
+
+extern fn classify_public_key(public_key: [*]const u8) KeyValidity;
+
+pub fn classifyPublicKey(public_key: types.BandersnatchPublic) KeyValidity {
+    return classify_public_key(&public_key);
+}
+
 //  _   _       _ _     _____         _
 // | | | |_ __ (_) |_  |_   _|__  ___| |_ ___
 // | | | | '_ \| | __|   | |/ _ \/ __| __/ __|
@@ -415,3 +476,25 @@ test "ring_vrf.ietf: IETF VRF usage" {
     std.debug.print("IETF VRF signature: {x}\n", .{ietf_signature});
     std.debug.print("IETF VRF output: {x}\n", .{vrf_output});
 }
+
+test "ring_vrf.keys: classify public keys" {
+    const key_pair = try bandersnatch.Bandersnatch.KeyPair.generateDeterministic("classify");
+    const public_key = key_pair.public_key.toBytes();
+    try std.testing.expectEqual(KeyValidity.in_prime_subgroup, classifyPublicKey(public_key));
+    try std.testing.expectEqual(KeyValidity.invalid, classifyPublicKey([_]u8{0xff} ** 32));
+
+    // (0, -1) has order 2, it is on the curve but not in the prime subgroup
+    var small_order: types.BandersnatchPublic = undefined;
+    _ = try std.fmt.hexToBytes(&small_order, "00000000fffffffffe5bfeff02a4bd5305d8a10908d83933487d9d2953a7ed73");
+    try std.testing.expectEqual(KeyValidity.on_curve, classifyPublicKey(small_order));
+
+    var padded: [2]bool = undefined;
+    var verifier = try RingVerifier.initReportPadding(&.{ public_key, [_]u8{0xff} ** 32 }, &padded);
+    defer verifier.deinit();
+    try std.testing.expectEqualSlices(bool, &.{ false, true }, &padded);
+
+    var prover_padded: [2]bool = undefined;
+    var prover = try RingProver.initReportPadding(key_pair.secret_key.toBytes(), &.{ [_]u8{0xff} ** 32, public_key }, 1, &prover_padded);
+    defer prover.deinit();
+    try std.testing.expectEqualSlices(bool, &.{ true, false }, &prover_padded);
+}
@@ -172,85 +172,6 @@ const ValidatorKeySet = struct {
     }
 };
 
-// Manages entropy generation and VRF output for block production
-const EntropyManager = struct {
-    pub fn generateVrfOutputFallback(author_keys: *const ValidatorKeySet, eta_prime: *const types.Eta) !types.BandersnatchVrfOutput {
-        const span = trace.span(@src(), .generate_vrf_output_fallback);
-        defer span.deinit();
-        span.debug("Generating VRF output using fallback function", .{});
-
-        const context = "jam_fallback_seal" ++ eta_prime[3];
-        span.trace("Using eta_prime[3]: {any}", .{std.fmt.fmtSliceHexLower(&eta_prime[3])});
-        span.trace("Using context: {s}", .{context});
-
-        span.debug("Signing with Bandersnatch keypair", .{});
-        span.trace("Using public key: {any}", .{std.fmt.fmtSliceHexLower(&author_keys.bandersnatch_keypair.public_key.toBytes())});
-        const signature = try author_keys.bandersnatch_keypair
-            .sign(context, &[_]u8{});
-        span.trace("Generated signature: {s}", .{std.fmt.fmtSliceHexLower(&signature.toBytes())});
-
-        const output = try signature.outputHash();
-        span.debug("Generated VRF output", .{});
-        span.trace("VRF output: {s}", .{std.fmt.fmtSliceHexLower(&output)});
-
-        return output;
-    }
-
-    pub fn generateEntropySourceFallback(
-        author_keys: ValidatorKeySet,
-        eta_prime: *const types.Eta,
-    ) !Bandersnatch.Signature {
-        const span = trace.span(@src(), .generate_entropy_source_fallback);
-        defer span.deinit();
-        span.debug("Generating entropy source using fallback method", .{});
-
-        span.debug("Generating VRF output", .{});
-        const vrf_output = try generateVrfOutputFallback(&author_keys, eta_prime);
-        span.trace("VRF output: {any}", .{std.fmt.fmtSliceHexLower(&vrf_output)});
-
-        // Now sign with our Bandersnatch keypair
-        const context = "jam_entropy" ++ vrf_output;
-        span.trace("Signing context: {s}", .{context});
-        span.trace("Using public key: {any}", .{std.fmt.fmtSliceHexLower(&author_keys.bandersnatch_keypair.public_key.toBytes())});
-
-        span.debug("Generating Bandersnatch signature", .{});
-        const entropy_source = try author_keys.bandersnatch_keypair
-            .sign(context, &[_]u8{});
-
-        span.debug("Generated entropy source signature", .{});
-        span.trace("Entropy source bytes: {any}", .{std.fmt.fmtSliceHexLower(&entropy_source.toBytes())});
-
-        return entropy_source;
-    }
-
-    pub fn generateEntropySourceTicket(
-        author_keys: ValidatorKeySet,
-        ticket: types.TicketBody,
-    ) !Bandersnatch.Signature {
-        const span = trace.span(@src(), .generate_entropy_source_ticket);
-        defer span.deinit();
-        span.debug("Generating entropy source using ticket method", .{});
-
-        // Now sign with our Bandersnatch keypair
-        const context = "jam_entropy" ++ ticket.id;
-        span.trace("Signing context: {s}", .{context});
-        span.trace("Using public key: {any}", .{std.fmt.fmtSliceHexLower(&author_keys.bandersnatch_keypair.public_key.toBytes())});
-
-        span.debug("Generating Bandersnatch signature", .{});
-        const entropy_source = try author_keys.bandersnatch_keypair
-            .sign(context, &[_]u8{});
-
-        span.debug("Generated entropy source signature", .{});
-        span.trace("Entropy source bytes: {any}", .{std.fmt.fmtSliceHexLower(&entropy_source.toBytes())});
-
-        return entropy_source;
-    }
-
-    pub fn updateEntropy(current_eta: types.Eta, entropy_source: types.BandersnatchVrfOutput) types.Hash {
-        return @import("entropy.zig").update(current_eta[0], entropy_source);
-    }
-};
-
 // Manages ticket registries for tracking validator tickets across epochs
 const TicketRegistry = struct {
     const Entry = struct {
@@ -318,25 +239,24 @@ const TicketGenerationParams = struct {
     gamma_z: types.Hash,
 };
 
-// Generates block seals using different strategies (fallback vs ticket-based)
+// Generates the entropy source and seal of a block
 const BlockSealGenerator = struct {
-    pub fn generateBlockSealFallback(
+    /// Sign H_v and H_s over the unsigned header in one crypto call, the
+    /// seal and entropy contexts are built by the crypto library
+    pub fn generateBlockSeal(
         allocator: std.mem.Allocator,
-        header: *const types.Header, // Assumes entropy_source is already set
+        header: *const types.Header, // entropy_source is overwritten by the crypto library
         author_keys: ValidatorKeySet,
         eta_prime: *const types.Eta,
+        mode: Bandersnatch.SealMode,
         comptime params: jam_params.Params,
-    ) !Bandersnatch.Signature {
-        const span = trace.span(@src(), .generate_seal_fallback);
+    ) !Bandersnatch.BlockSeal {
+        const span = trace.span(@src(), .generate_block_seal);
         defer span.deinit();
-        span.debug("Generating block seal using fallback method", .{});
-
-        const context = "jam_fallback_seal" ++ eta_prime[3];
+        span.debug("Generating block seal in {s} mode", .{@tagName(mode)});
         span.trace("Using eta_prime[3]: {any}", .{std.fmt.fmtSliceHexLower(&eta_prime[3])});
-        span.trace("Using context: {s}", .{context});
         span.trace("Using author public key: {any}", .{std.fmt.fmtSliceHexLower(&author_keys.bandersnatch_keypair.public_key.toBytes())});
 
-        // Create bandersnatch signature
         span.debug("Serializing unsigned header", .{});
         const header_unsigned = try codec.serializeAlloc(
             types.HeaderUnsigned,
@@ -347,54 +267,28 @@ const BlockSealGenerator = struct {
         defer allocator.free(header_unsigned);
         span.trace("Unsigned header bytes: {any}", .{std.fmt.fmtSliceHexLower(header_unsigned)});
 
-        // Generate and return the seal signature
-        span.debug("Generating Bandersnatch signature", .{});
-        const signature = try author_keys.bandersnatch_keypair
-            .sign(context, header_unsigned);
-
-        span.debug("Generated block seal signature", .{});
-        span.trace("Seal signature bytes: {any}", .{std.fmt.fmtSliceHexLower(&signature.toBytes())});
-
-        return signature;
-    }
-
-    pub fn generateBlockSealTickets(
-        allocator: std.mem.Allocator,
-        header: *const types.Header, // Assumes entropy_source is already set
-        author_keys: ValidatorKeySet,
-        eta_prime: *const types.Eta,
-        ticket: types.TicketBody,
-        comptime params: jam_params.Params,
-    ) !Bandersnatch.Signature {
-        const span = trace.span(@src(), .generate_seal_tickets);
-        defer span.deinit();
-        span.debug("Generating block seal using tickets method", .{});
-
-        const context = "jam_ticket_seal" ++ eta_prime[3] ++ [_]u8{ticket.attempt};
-        span.trace("Using eta_prime[3]: {any}", .{std.fmt.fmtSliceHexLower(&eta_prime[3])});
-        span.trace("Using context: {s}", .{context});
-        span.trace("Using author public key: {any}", .{std.fmt.fmtSliceHexLower(&author_keys.bandersnatch_keypair.public_key.toBytes())});
-
-        // Create bandersnatch signature
-        span.debug("Serializing unsigned header", .{});
-        const header_unsigned = try codec.serializeAlloc(
-            types.HeaderUnsigned,
+        // H_v is followed only by the offenders mark in the unsigned header
+        const header_tail = try codec.serializeAlloc(
+            struct { entropy_source: types.BandersnatchVrfSignature, offenders_mark: []types.Ed25519Public },
             params,
             allocator,
-            types.HeaderUnsigned.fromHeaderShared(header),
+            .{ .entropy_source = header.entropy_source, .offenders_mark = header.offenders_mark },
+        );
+        defer allocator.free(header_tail);
+
+        span.debug("Generating Bandersnatch signatures", .{});
+        const block_seal = try author_keys.bandersnatch_keypair.signBlockSeal(
+            header_unsigned,
+            header_unsigned.len - header_tail.len,
+            eta_prime[3],
+            mode,
         );
-        defer allocator.free(header_unsigned);
-        span.trace("Unsigned header bytes: {any}", .{std.fmt.fmtSliceHexLower(header_unsigned)});
-
-        // Generate and return the seal signature
-        span.debug("Generating Bandersnatch signature", .{});
-        const signature = try author_keys.bandersnatch_keypair
-            .sign(context, header_unsigned);
 
-        span.debug("Generated block seal signature", .{});
-        span.trace("Seal signature bytes: {any}", .{std.fmt.fmtSliceHexLower(&signature.toBytes())});
+        span.debug("Generated entropy source and block seal signatures", .{});
+        span.trace("Entropy source bytes: {any}", .{std.fmt.fmtSliceHexLower(&block_seal.entropy_source.toBytes())});
+        span.trace("Seal signature bytes: {any}", .{std.fmt.fmtSliceHexLower(&block_seal.seal.toBytes())});
 
-        return signature;
+        return block_seal;
     }
 };
 
@@ -636,24 +530,20 @@ pub fn BlockBuilder(comptime params: jam_params.Params) type {
             author_keys: ValidatorKeySet,
             eta_prime: *const types.Eta,
         ) !void {
-            const block_seal = switch (gamma_s_prime) {
-                .keys => try BlockSealGenerator.generateBlockSealFallback(
-                    self.allocator,
-                    header,
-                    author_keys,
-                    eta_prime,
-                    params,
-                ),
-                .tickets => |t| try BlockSealGenerator.generateBlockSealTickets(
-                    self.allocator,
-                    header,
-                    author_keys,
-                    eta_prime,
-                    t[self.block_time.current_slot_in_epoch],
-                    params,
-                ),
+            const mode: Bandersnatch.SealMode = switch (gamma_s_prime) {
+                .keys => .fallback,
+                .tickets => |t| .{ .ticket = t[self.block_time.current_slot_in_epoch].attempt },
             };
-            header.seal = block_seal.toBytes();
+            const block_seal = try BlockSealGenerator.generateBlockSeal(
+                self.allocator,
+                header,
+                author_keys,
+                eta_prime,
+                mode,
+                params,
+            );
+            header.entropy_source = block_seal.entropy_source.toBytes();
+            header.seal = block_seal.seal.toBytes();
         }
 
         // Build the next block in the chain with proper sealing
@@ -679,7 +569,7 @@ pub fn BlockBuilder(comptime params: jam_params.Params) type {
 
             // Calculate eta_prime (taking into account if this is a newEpoch)
             const eta_current = &self.state.eta.?;
-            var eta_prime_without_eta_0 = self.calculateEtaPrimeWithoutEta0(eta_current);
+            const eta_prime_without_eta_0 = self.calculateEtaPrimeWithoutEta0(eta_current);
 
             // Determine gamma_s_prime for block production
             var r = try self.determineGammaSPrime(
@@ -698,15 +588,9 @@ pub fn BlockBuilder(comptime params: jam_params.Params) type {
             const epoch_mark = try self.prepareEpochMark();
             const tickets_mark = try self.prepareTicketsMark();
 
-            const entropy_source = switch (gamma_s_prime) {
-                .tickets => |tickets| try EntropyManager.generateEntropySourceTicket(author_keys, tickets[self.block_time.current_slot_in_epoch]),
-                .keys => try EntropyManager.generateEntropySourceFallback(author_keys, &eta_prime_without_eta_0),
-            };
-            eta_prime_without_eta_0[0] = EntropyManager.updateEntropy(self.state.eta.?, try entropy_source.outputHash());
-            const eta_prime = eta_prime_without_eta_0;
-
-            // Generate extrinsic content
-            const extrinsic = try self.generateBlockContent(&eta_prime);
+            // Generate extrinsic content, tickets only depend on eta_prime[2]
+            // so eta_prime[0] is not needed before the block is sealed
+            const extrinsic = try self.generateBlockContent(&eta_prime_without_eta_0);
 
             // Create header
             var header = types.Header{
@@ -718,11 +602,11 @@ pub fn BlockBuilder(comptime params: jam_params.Params) type {
                 .epoch_mark = epoch_mark,
                 .tickets_mark = tickets_mark,
                 .offenders_mark = &[_]types.Ed25519Public{},
-                .entropy_source = entropy_source.toBytes(),
+                .entropy_source = std.mem.zeroes(types.BandersnatchVrfSignature),
                 .seal = undefined,
             };
 
-            // Seal the block
+            // Sign the entropy source and seal the block
             try self.sealBlock(&header, gamma_s_prime, author_keys, &eta_prime_without_eta_0);
 
             // Assemble complete block